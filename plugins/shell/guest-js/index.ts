@@ -560,6 +560,8 @@ interface TerminatedPayload {
   code: number | null
   /** If the process was terminated by a signal, represents that signal. */
   signal: number | null
+  /** How long the process ran, in milliseconds. */
+  duration_ms: number
 }
 
 /** Event payload type */
@@ -4,7 +4,7 @@
 
 use std::{collections::HashMap, future::Future, path::PathBuf, pin::Pin, string::FromUtf8Error};
 
-use encoding_rs::Encoding;
+use encoding_rs::{Decoder, Encoding};
 use serde::{Deserialize, Serialize};
 use tauri::{
     ipc::{Channel, CommandScope, GlobalScope},
@@ -47,16 +47,168 @@ fn get_event_buffer(line: Vec<u8>, encoding: EncodingWrapper) -> Result<Buffer,
 }
 
 impl JSCommandEvent {
-    pub fn new(event: CommandEvent, encoding: EncodingWrapper) -> Self {
-        match event {
+    /// Converts a command event to the event sent to the frontend,
+    /// returns `None` for events that are not exposed to JavaScript.
+    pub fn new(event: CommandEvent, encoding: EncodingWrapper) -> Option<Self> {
+        let event = match event {
             CommandEvent::Terminated(payload) => JSCommandEvent::Terminated(payload),
             CommandEvent::Error(error) => JSCommandEvent::Error(error),
-            CommandEvent::Stderr(line) => get_event_buffer(line, encoding)
+            CommandEvent::Stderr(line) => get_event_buffer(line.into(), encoding)
+                .map(JSCommandEvent::Stderr)
+                .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
+            CommandEvent::Stdout(line) => get_event_buffer(line.into(), encoding)
+                .map(JSCommandEvent::Stdout)
+                .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
+            CommandEvent::StderrBatch(lines) => get_event_buffer(lines.concat(), encoding)
                 .map(JSCommandEvent::Stderr)
                 .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
-            CommandEvent::Stdout(line) => get_event_buffer(line, encoding)
+            CommandEvent::StdoutBatch(lines) => get_event_buffer(lines.concat(), encoding)
                 .map(JSCommandEvent::Stdout)
                 .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
+            CommandEvent::FirstOutput { .. } => return None,
+        };
+        Some(event)
+    }
+}
+
+/// The UTF-8, UTF-16LE and UTF-16BE byte order marks.
+const BOMS: [&[u8]; 3] = [b"\xEF\xBB\xBF", b"\xFF\xFE", b"\xFE\xFF"];
+
+/// Decodes a stream of output progressively, keeping incomplete multibyte sequences
+/// at the end of a chunk until the next chunk is received.
+///
+/// A byte order mark at the start of the stream is stripped,
+/// and a UTF-16 one switches the decoder to the matching encoding.
+struct StreamDecoder {
+    /// `None` for strict UTF-8, which fails on invalid sequences instead of replacing them.
+    decoder: Option<Decoder>,
+    /// The incomplete UTF-8 sequence at the end of the last chunk, when decoding strict UTF-8.
+    pending: Vec<u8>,
+    started: bool,
+}
+
+impl StreamDecoder {
+    fn new(encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            // sniffs the BOM and switches to the encoding it denotes
+            decoder: encoding.map(Encoding::new_decoder),
+            pending: Vec::new(),
+            started: false,
+        }
+    }
+
+    fn decode(&mut self, bytes: &[u8], last: bool) -> Result<String, FromUtf8Error> {
+        let mut bytes = bytes;
+        let buffered;
+        if !self.started && self.decoder.is_none() {
+            buffered = [std::mem::take(&mut self.pending).as_slice(), bytes].concat();
+            bytes = &buffered;
+            // the BOM might be split across reads, e.g. with a small raw chunk size
+            if !last
+                && BOMS
+                    .iter()
+                    .any(|bom| bom.len() > bytes.len() && bom.starts_with(bytes))
+            {
+                self.pending = buffered;
+                return Ok(String::new());
+            }
+            self.started = true;
+            if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+                bytes = &bytes[bom_length..];
+                if encoding != encoding_rs::UTF_8 {
+                    self.decoder = Some(encoding.new_decoder_without_bom_handling());
+                }
+            }
+        }
+
+        match &mut self.decoder {
+            Some(decoder) => {
+                let capacity = decoder
+                    .max_utf8_buffer_length(bytes.len())
+                    .unwrap_or(bytes.len());
+                let mut text = String::with_capacity(capacity);
+                let _ = decoder.decode_to_string(bytes, &mut text, last);
+                Ok(text)
+            }
+            None => {
+                let mut bytes = [std::mem::take(&mut self.pending).as_slice(), bytes].concat();
+                match String::from_utf8(bytes) {
+                    // the chunk ends in the middle of a character, which is completed by the next chunk
+                    Err(e) if !last && e.utf8_error().error_len().is_none() => {
+                        let valid = e.utf8_error().valid_up_to();
+                        bytes = e.into_bytes();
+                        self.pending = bytes.split_off(valid);
+                        String::from_utf8(bytes)
+                    }
+                    decoded => decoded,
+                }
+            }
+        }
+    }
+}
+
+/// Decodes the stdout and stderr output of a process, see [`StreamDecoder`].
+struct OutputDecoder {
+    stdout: StreamDecoder,
+    stderr: StreamDecoder,
+}
+
+impl OutputDecoder {
+    fn new(encoding: Option<&'static Encoding>) -> Self {
+        Self {
+            stdout: StreamDecoder::new(encoding),
+            stderr: StreamDecoder::new(encoding),
+        }
+    }
+
+    fn text_event(
+        decoded: Result<String, FromUtf8Error>,
+        event: fn(Buffer) -> JSCommandEvent,
+    ) -> Option<JSCommandEvent> {
+        match decoded {
+            Ok(text) if text.is_empty() => None,
+            Ok(text) => Some(event(Buffer::Text(text))),
+            Err(e) => Some(JSCommandEvent::Error(e.to_string())),
+        }
+    }
+
+    /// Converts a command event to the events sent to the frontend.
+    ///
+    /// The trailing bytes of both streams are flushed when the process terminates.
+    fn events(&mut self, event: CommandEvent) -> Vec<JSCommandEvent> {
+        match event {
+            CommandEvent::Stdout(bytes) => {
+                Self::text_event(self.stdout.decode(&bytes, false), JSCommandEvent::Stdout)
+                    .into_iter()
+                    .collect()
+            }
+            CommandEvent::Stderr(bytes) => {
+                Self::text_event(self.stderr.decode(&bytes, false), JSCommandEvent::Stderr)
+                    .into_iter()
+                    .collect()
+            }
+            CommandEvent::StdoutBatch(chunks) => {
+                self.events(CommandEvent::Stdout(chunks.concat().into()))
+            }
+            CommandEvent::StderrBatch(chunks) => {
+                self.events(CommandEvent::Stderr(chunks.concat().into()))
+            }
+            CommandEvent::Terminated(payload) => {
+                let mut events = Vec::new();
+                events.extend(Self::text_event(
+                    self.stdout.decode(&[], true),
+                    JSCommandEvent::Stdout,
+                ));
+                events.extend(Self::text_event(
+                    self.stderr.decode(&[], true),
+                    JSCommandEvent::Stderr,
+                ));
+                events.push(JSCommandEvent::Terminated(payload));
+                events
+            }
+            event => JSCommandEvent::new(event, EncodingWrapper::Raw)
+                .into_iter()
+                .collect(),
         }
     }
 }
@@ -201,13 +353,9 @@ pub async fn execute<R: Runtime>(
     let output = command.output()?;
 
     let (stdout, stderr) = match encoding {
-        EncodingWrapper::Text(Some(encoding)) => (
-            Output::String(encoding.decode_with_bom_removal(&output.stdout).0.into()),
-            Output::String(encoding.decode_with_bom_removal(&output.stderr).0.into()),
-        ),
-        EncodingWrapper::Text(None) => (
-            Output::String(String::from_utf8(output.stdout)?),
-            Output::String(String::from_utf8(output.stderr)?),
+        EncodingWrapper::Text(encoding) => (
+            Output::String(StreamDecoder::new(encoding).decode(&output.stdout, true)?),
+            Output::String(StreamDecoder::new(encoding).decode(&output.stderr, true)?),
         ),
         EncodingWrapper::Raw => (Output::Raw(output.stdout), Output::Raw(output.stderr)),
     };
@@ -247,26 +395,37 @@ pub fn spawn<R: Runtime>(
     shell.children.lock().unwrap().insert(pid, child);
     let children = shell.children.clone();
 
+    // text is decoded progressively so multibyte characters split across events are not broken
+    let mut decoder = match encoding {
+        EncodingWrapper::Text(encoding) => Some(OutputDecoder::new(encoding)),
+        EncodingWrapper::Raw => None,
+    };
+
     tauri::async_runtime::spawn(async move {
         while let Some(event) = rx.recv().await {
             if matches!(event, crate::process::CommandEvent::Terminated(_)) {
                 children.lock().unwrap().remove(&pid);
             };
-            let js_event = JSCommandEvent::new(event, encoding);
-
-            if on_event.send(js_event.clone()).is_err() {
-                fn send<'a>(
-                    on_event: &'a Channel<JSCommandEvent>,
-                    js_event: &'a JSCommandEvent,
-                ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
-                    Box::pin(async move {
-                        tokio::time::sleep(std::time::Duration::from_millis(15)).await;
-                        if on_event.send(js_event.clone()).is_err() {
-                            send(on_event, js_event).await;
-                        }
-                    })
+            let js_events = match &mut decoder {
+                Some(decoder) => decoder.events(event),
+                None => JSCommandEvent::new(event, encoding).into_iter().collect(),
+            };
+
+            for js_event in js_events {
+                if on_event.send(js_event.clone()).is_err() {
+                    fn send<'a>(
+                        on_event: &'a Channel<JSCommandEvent>,
+                        js_event: &'a JSCommandEvent,
+                    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+                        Box::pin(async move {
+                            tokio::time::sleep(std::time::Duration::from_millis(15)).await;
+                            if on_event.send(js_event.clone()).is_err() {
+                                send(on_event, js_event).await;
+                            }
+                        })
+                    }
+                    send(&on_event, &js_event).await;
                 }
-                send(&on_event, &js_event).await;
             }
         }
     });
@@ -311,3 +470,74 @@ pub fn open<R: Runtime>(
 ) -> crate::Result<()> {
     shell.open(path, with)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_decoder_split_character() {
+        // "日本" is 6 bytes in UTF-8, split inside both characters
+        let text = "日本".as_bytes();
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(&text[..2], false).unwrap(), "");
+        assert_eq!(decoder.decode(&text[2..4], false).unwrap(), "日");
+        assert_eq!(decoder.decode(&text[4..], false).unwrap(), "本");
+        assert_eq!(decoder.decode(&[], true).unwrap(), "");
+
+        let text = encoding_rs::SHIFT_JIS.encode("日本").0;
+        let mut decoder = StreamDecoder::new(Some(encoding_rs::SHIFT_JIS));
+        assert_eq!(decoder.decode(&text[..1], false).unwrap(), "");
+        assert_eq!(decoder.decode(&text[1..3], false).unwrap(), "日");
+        assert_eq!(decoder.decode(&text[3..], true).unwrap(), "本");
+    }
+
+    #[test]
+    fn test_stream_decoder_flush_incomplete() {
+        let text = "日".as_bytes();
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(&text[..2], false).unwrap(), "");
+        // the output ended in the middle of a character
+        assert!(decoder.decode(&[], true).is_err());
+
+        let mut decoder = StreamDecoder::new(Some(encoding_rs::UTF_8));
+        assert_eq!(decoder.decode(&text[..2], false).unwrap(), "");
+        assert_eq!(decoder.decode(&[], true).unwrap(), "\u{FFFD}");
+
+        // invalid sequences still fail right away with strict UTF-8
+        let mut decoder = StreamDecoder::new(None);
+        assert!(decoder.decode(b"a\xFFb", false).is_err());
+    }
+
+    #[test]
+    fn test_stream_decoder_bom() {
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(b"\xEF\xBB\xBFa", false).unwrap(), "a");
+        assert_eq!(decoder.decode(b"\xEF\xBB\xBFb", true).unwrap(), "\u{FEFF}b");
+
+        // split across the first reads
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(b"\xEF", false).unwrap(), "");
+        assert_eq!(decoder.decode(b"\xBB", false).unwrap(), "");
+        assert_eq!(decoder.decode(b"\xBFa", true).unwrap(), "a");
+
+        let utf16le = [0xFF, 0xFE, b'h', 0, b'i', 0];
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(&utf16le[..1], false).unwrap(), "");
+        assert_eq!(decoder.decode(&utf16le[1..5], false).unwrap(), "h");
+        assert_eq!(decoder.decode(&utf16le[5..], true).unwrap(), "i");
+
+        let utf16be = [0xFE, 0xFF, 0, b'h', 0, b'i'];
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(&utf16be, true).unwrap(), "hi");
+
+        // output that only looks like the start of a BOM is decoded at the end
+        let mut decoder = StreamDecoder::new(Some(encoding_rs::WINDOWS_1252));
+        assert_eq!(decoder.decode(b"\xEF", true).unwrap(), "\u{EF}");
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(b"\xEF", false).unwrap(), "");
+        assert!(decoder.decode(&[], true).is_err());
+        let mut decoder = StreamDecoder::new(None);
+        assert_eq!(decoder.decode(b"ab", false).unwrap(), "ab");
+    }
+}
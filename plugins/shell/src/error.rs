@@ -25,8 +25,54 @@ pub enum Error {
     /// Program not allowed by the scope.
     #[error("program not allowed on the configured shell scope: {0}")]
     ProgramNotAllowed(PathBuf),
+    /// Neither the program of a command nor its fallbacks could be found.
+    #[error(
+        "program not found, tried {}",
+        .0.iter().map(|program| program.to_string_lossy()).collect::<Vec<_>>().join(", ")
+    )]
+    ProgramNotFound(Vec<std::ffi::OsString>),
     #[error("unknown encoding {0}")]
     UnknownEncoding(String),
+    /// The command line couldn't be split into a program and its arguments, see `Command::parse`.
+    #[error("invalid command line: {0}")]
+    InvalidCommandLine(String),
+    /// The command event stream was closed, meaning no more events will be received.
+    #[error("command event stream closed")]
+    EventStreamClosed,
+    /// The operation requires the process to have exited.
+    #[error("the process is still running")]
+    ProcessRunning,
+    /// The command exited with a non-zero status, includes the tail of its stderr output.
+    #[error(
+        "command exited with status {code:?}: {}",
+        String::from_utf8_lossy(stderr)
+    )]
+    NonZeroExit {
+        /// The exit code of the process, `None` if it was terminated by a signal.
+        code: Option<i32>,
+        /// The last bytes the process wrote to stderr.
+        stderr: Vec<u8>,
+    },
+    /// The command exited with a code registered with `Command::exit_code_map`.
+    #[error("command exited with status {code}: {meaning}")]
+    KnownExit {
+        /// The exit code of the process.
+        code: i32,
+        /// The documented meaning of the exit code.
+        meaning: String,
+    },
+    /// The command didn't finish before the timeout and was killed, includes the output collected until then.
+    #[error("command timed out")]
+    TimedOut(Box<crate::process::Output>),
+    /// The process exited before its output matched the pattern, see `Command::spawn_until_match`.
+    #[error("the process exited before its output matched {0}")]
+    NoMatch(String),
+    /// The output didn't match the pattern before the timeout and the process was killed, see `Command::spawn_until_match_timeout`.
+    #[error("the process output didn't match {0} before the timeout")]
+    MatchTimedOut(String),
+    /// Invalid regular expression.
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
     /// JSON error.
     #[error(transparent)]
     Json(#[from] serde_json::Error),
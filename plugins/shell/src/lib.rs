@@ -34,6 +34,8 @@ mod scope;
 mod scope_entry;
 
 pub use error::Error;
+#[cfg(feature = "diagnostics")]
+pub use process::active_reader_threads;
 type Result<T> = std::result::Result<T, Error>;
 
 #[cfg(mobile)]
@@ -60,10 +62,26 @@ impl<R: Runtime> Shell<R> {
         Command::new(program)
     }
 
+    /// Creates a new Command for launching the given program with the exact argument vector `argv`.
+    ///
+    /// Unlike `command(program).args(...)`, the first element of `argv` is passed verbatim as the
+    /// process `argv[0]` instead of the program path, and the remaining elements are its arguments.
+    /// This is useful for shims and multi-call binaries that inspect the name they were invoked with.
+    ///
+    /// On Windows the process name can't be decoupled from the program,
+    /// so the first element of `argv` is ignored.
+    pub fn command_with_argv(&self, program: impl Into<String>, argv: Vec<String>) -> Command {
+        Command::with_argv(program.into(), argv)
+    }
+
     /// Creates a new Command for launching the given sidecar program.
     ///
     /// A sidecar program is a embedded external binary in order to make your application work
     /// or to prevent users having to install additional dependencies (e.g. Node.js, Python, etc).
+    ///
+    /// The program is looked up next to the current executable. If it's not there, the variant suffixed with
+    /// the target triple is used instead when it exists, e.g. `my-binary-x86_64-unknown-linux-gnu`.
+    /// The resolved path is returned by [`Command::get_program`].
     pub fn sidecar(&self, program: impl AsRef<Path>) -> Result<Command> {
         Command::new_sidecar(program)
     }
@@ -129,18 +147,23 @@ pub fn init<R: Runtime>() -> TauriPlugin<R, Option<config::Config>> {
         .on_event(|app, event| {
             if let RunEvent::Exit = event {
                 let shell = app.state::<Shell<R>>();
-                let children = {
-                    let mut lock = shell.children.lock().unwrap();
-                    std::mem::take(&mut *lock)
-                };
-                for child in children.into_values() {
-                    let _ = child.kill();
-                }
+                kill_children(&shell.children);
             }
         })
         .build()
 }
 
+/// Kills all the tracked children, leaving the store empty.
+fn kill_children(children: &ChildStore) {
+    let children = {
+        let mut lock = children.lock().unwrap();
+        std::mem::take(&mut *lock)
+    };
+    for child in children.into_values() {
+        let _ = child.kill();
+    }
+}
+
 fn open_scope(open: &config::ShellAllowlistOpen) -> scope::OpenScope {
     let shell_scope_open = match open {
         config::ShellAllowlistOpen::Flag(false) => None,
@@ -159,3 +182,39 @@ fn open_scope(open: &config::ShellAllowlistOpen) -> scope::OpenScope {
         open: shell_scope_open,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use process::{recv_timeout, CommandEvent};
+
+    #[test]
+    fn test_kill_children() {
+        #[cfg(not(windows))]
+        let command = Command::new("sleep").args(["30"]);
+        #[cfg(windows)]
+        let command = Command::new("ping").args(["-n", "30", "127.0.0.1"]);
+
+        let (mut rx, child) = command.spawn().unwrap();
+        let children = ChildStore::default();
+        children.lock().unwrap().insert(child.pid(), child);
+
+        kill_children(&children);
+        assert!(children.lock().unwrap().is_empty());
+
+        tauri::async_runtime::block_on(async move {
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap() {
+                    Some(CommandEvent::Terminated(payload)) => {
+                        assert_ne!(payload.code, Some(0));
+                        break;
+                    }
+                    Some(_) => {}
+                    None => panic!("child was not killed"),
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Splits a command line into words, see [`super::Command::parse`].
+
+/// Splits `cmdline` into words following the quoting rules of a POSIX shell.
+///
+/// Single quotes keep everything up to the closing quote literally. Inside double quotes a backslash only
+/// escapes `$`, `` ` ``, `"`, `\` and newlines, outside of quotes it escapes any character.
+/// An escaped newline is removed. Nothing is expanded, and there are no operators or comments.
+pub(crate) fn split(cmdline: &str) -> crate::Result<Vec<String>> {
+    let invalid = |reason: &str| crate::Error::InvalidCommandLine(reason.into());
+    let mut words = Vec::new();
+    // `None` between words, so quoted empty words are kept
+    let mut word: Option<String> = None;
+    let mut chars = cmdline.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(invalid("unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(invalid("trailing backslash")),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
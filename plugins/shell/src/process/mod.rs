@@ -3,30 +3,93 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    ffi::OsStr,
-    io::{BufRead, BufReader, Write},
+    collections::{BTreeMap, HashMap},
+    ffi::{OsStr, OsString},
+    fs::File,
+    future::Future,
+    io::{Read, Write},
     path::{Path, PathBuf},
+    pin::Pin,
     process::{Command as StdCommand, Stdio},
-    sync::{Arc, RwLock},
-    thread::spawn,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, OnceLock, Weak,
+    },
+    task::{Context, Poll, Wake},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
+#[cfg(unix)]
+use shared_child::unix::SharedChildExt;
 #[cfg(unix)]
 use std::os::unix::process::ExitStatusExt;
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod seccomp;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use seccomp::SeccompFilter;
+
+mod cmdline;
+mod priority;
+#[cfg(all(unix, feature = "pty"))]
+mod pty;
+#[cfg(any(unix, windows))]
+mod tree;
+#[cfg(windows)]
+mod windows;
+
+pub use priority::Priority;
+use priority::{DispatchSender, Dispatcher};
+
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+#[cfg(windows)]
+const DETACHED_PROCESS: u32 = 0x0000_0008;
+#[cfg(windows)]
+const CREATE_SUSPENDED: u32 = 0x0000_0004;
+#[cfg(windows)]
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+#[cfg(windows)]
+const NORMAL_PRIORITY_CLASS: u32 = 0x0000_0020;
+#[cfg(windows)]
+const HIGH_PRIORITY_CLASS: u32 = 0x0000_0080;
 const NEWLINE_BYTE: u8 = b'\n';
+/// The maximum size of the output events when [`Command::set_raw_out`] is enabled.
+const DEFAULT_RAW_CHUNK_SIZE: usize = 4096;
 
-use tauri::async_runtime::{block_on as block_on_task, channel, Receiver, Sender};
+use tauri::async_runtime::{
+    block_on as block_on_task, channel, JoinHandle as TaskHandle, Receiver, Sender,
+};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader, ReadBuf},
+    sync::{mpsc::WeakSender, watch},
+};
 
+pub use bytes::Bytes;
+use encoding_rs::Decoder;
 pub use encoding_rs::Encoding;
+use futures_core::Stream;
 use os_pipe::{pipe, PipeReader, PipeWriter};
 use serde::Serialize;
-use shared_child::SharedChild;
+pub use shared_child::SharedChild;
 use tauri::utils::platform;
+pub use tokio::sync::broadcast;
+pub use tokio_util::sync::CancellationToken;
+
+/// The [`TerminatedPayload::signal`] reported for a process killed by its cancellation token,
+/// see [`Command::spawn_with_cancel`].
+///
+/// This is the null signal, which can't terminate a process, so it's never reported otherwise.
+pub const CANCELLED_SIGNAL: i32 = 0;
 
 /// Payload for the [`CommandEvent::Terminated`] command event.
 #[derive(Debug, Clone, Serialize)]
@@ -34,541 +97,7154 @@ pub struct TerminatedPayload {
     /// Exit code of the process.
     pub code: Option<i32>,
     /// If the process was terminated by a signal, represents that signal.
+    ///
+    /// This is [`CANCELLED_SIGNAL`], with no exit code, if the process was cancelled.
     pub signal: Option<i32>,
+    /// Best-effort path of the core dump file written when the process crashed.
+    ///
+    /// Only resolved on Linux, from `/proc/sys/kernel/core_pattern`.
+    /// This is `None` when the pattern pipes the core dump to a program (e.g. `systemd-coredump`)
+    /// or uses specifiers that can't be reproduced after the fact (e.g. `%t` or `%h`).
+    /// The file might also not exist if the core size limit (`ulimit -c`) prevented it from being written.
+    pub core_path: Option<PathBuf>,
+    /// How long the process ran, in milliseconds.
+    pub duration_ms: u64,
 }
 
 /// A event sent to the command callback.
+///
+/// The output is carried as [`Bytes`], so cloning an event doesn't copy it.
+/// Use `Vec::from(line)` where a `Vec<u8>` is needed, which reuses the allocation when possible.
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum CommandEvent {
     /// If configured for raw output, all bytes written to stderr.
     /// Otherwise, bytes until a newline (\n) or carriage return (\r) is found.
-    Stderr(Vec<u8>),
+    Stderr(Bytes),
     /// If configured for raw output, all bytes written to stdout.
     /// Otherwise, bytes until a newline (\n) or carriage return (\r) is found.
-    Stdout(Vec<u8>),
+    Stdout(Bytes),
     /// An error happened waiting for the command to finish or converting the stdout/stderr bytes to a UTF-8 string.
     Error(String),
     /// Command process terminated.
     Terminated(TerminatedPayload),
+    /// Chunks of stdout output coalesced by [`Command::max_events_per_sec`].
+    StdoutBatch(Vec<Bytes>),
+    /// Chunks of stderr output coalesced by [`Command::max_events_per_sec`].
+    StderrBatch(Vec<Bytes>),
+    /// The first byte of output was read from the process, see [`Command::measure_first_output`].
+    FirstOutput {
+        /// Time elapsed from the spawn call to the first byte of stdout or stderr output.
+        latency: Duration,
+    },
 }
 
 /// The type to spawn commands.
-#[derive(Debug)]
+///
+/// A configured command can be cloned to spawn it several times, e.g. in a retry loop.
+#[derive(Debug, Clone)]
 pub struct Command {
-    cmd: StdCommand,
+    program: OsString,
+    args: Vec<OsString>,
+    #[cfg(unix)]
+    arg0: Option<OsString>,
+    /// Explicit environment changes in call order, `None` removes the variable.
+    envs: Vec<(OsString, Option<OsString>)>,
+    current_dir: Option<PathBuf>,
+    #[cfg(unix)]
+    umask: Option<u32>,
+    #[cfg(unix)]
+    stdbuf_line: bool,
+    #[cfg(unix)]
+    uid: Option<u32>,
+    #[cfg(unix)]
+    gid: Option<u32>,
+    #[cfg(unix)]
+    process_group: bool,
+    /// The resource limits as `(resource, soft, hard)`, applied in order.
+    #[cfg(unix)]
+    rlimits: Vec<(Resource, u64, u64)>,
+    /// The `(rows, cols)` of the pseudo-terminal to attach the process to, see [`Self::spawn_pty`].
+    #[cfg(all(unix, feature = "pty"))]
+    pty: Option<(u16, u16)>,
+    #[cfg(windows)]
+    show_window: bool,
+    #[cfg(windows)]
+    creation_flags: u32,
+    #[cfg(windows)]
+    contain: bool,
+    /// Passes the arguments without quoting them, see [`Self::shell`].
+    #[cfg(windows)]
+    raw_args: bool,
+    priority: Option<ProcessPriority>,
     raw_out: bool,
+    raw_chunk_size: usize,
+    max_line_len: Option<usize>,
+    encoding: Option<&'static Encoding>,
+    line_prefix: Option<Vec<u8>>,
+    delimiter: Option<u8>,
+    normalize_newlines: bool,
+    restart_backoff: Option<RestartBackoff>,
+    measure_first_output: bool,
+    drain_on_kill: bool,
+    kill_on_drop: bool,
+    /// Capacity of the event channel.
+    event_buffer: usize,
+    max_events_per_sec: Option<u32>,
+    stream_priority: Option<Priority>,
+    tee_stdout: Option<Arc<File>>,
+    tee_stderr: Option<Arc<File>>,
+    /// Written to stdin right after spawning.
+    stdin_input: Option<StdinInput>,
+    exit_code_map: HashMap<i32, String>,
+    /// Initial capacity of the stdout and stderr buffers of [`Self::output`].
+    output_capacity: (usize, usize),
+    keep_last_bytes: Option<usize>,
+    /// Programs tried in order when the program can't be found, see [`Self::or_program`].
+    fallback_programs: Vec<OsString>,
+    /// Directories searched for the program before `PATH`, see [`Self::resolve_in`].
+    resolve_dirs: Vec<PathBuf>,
+    stdio: StdioConfig,
+    env_cleared: bool,
+    env_fn: Option<EnvHook>,
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    seccomp: Option<SeccompFilter>,
 }
 
-/// Spawned child process.
-#[derive(Debug)]
-pub struct CommandChild {
-    inner: Arc<SharedChild>,
-    stdin_writer: PipeWriter,
-}
-
-impl CommandChild {
-    /// Writes to process stdin.
-    pub fn write(&mut self, buf: &[u8]) -> crate::Result<()> {
-        self.stdin_writer.write_all(buf)?;
-        Ok(())
-    }
+/// A hook that modifies the environment of the child process right before it is spawned.
+///
+/// See [`Command::env_fn`].
+pub type EnvFn = Arc<dyn Fn(&str, &mut HashMap<String, String>) + Send + Sync>;
 
-    /// Sends a kill signal to the child.
-    pub fn kill(self) -> crate::Result<()> {
-        self.inner.kill()?;
-        Ok(())
-    }
+#[derive(Clone)]
+struct EnvHook(EnvFn);
 
-    /// Returns the process pid.
-    pub fn pid(&self) -> u32 {
-        self.inner.id()
+impl std::fmt::Debug for EnvHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvHook").finish_non_exhaustive()
     }
 }
 
-/// Describes the result of a process after it has terminated.
-#[derive(Debug)]
-pub struct ExitStatus {
-    code: Option<i32>,
+/// How a standard stream of the child process is connected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StdioMode {
+    /// Connected to a pipe, read by the event readers or written with [`CommandChild::write`].
+    Piped,
+    /// Shared with the parent process.
+    Inherit,
+    /// Written to a file, created or truncated when spawning.
+    File(PathBuf),
+    /// Connected to the null device.
+    Null,
+    /// Connected to the same pipe, file or device as stdout, see [`Command::merge_stderr_into_stdout`].
+    Stdout,
 }
 
-impl ExitStatus {
-    /// Returns the exit code of the process, if any.
-    pub fn code(&self) -> Option<i32> {
-        self.code
+impl StdioMode {
+    /// Creates the stream of the child process for the modes that don't use a pipe or a file.
+    fn unpiped(&self) -> Stdio {
+        match self {
+            StdioMode::Null => Stdio::null(),
+            _ => Stdio::inherit(),
+        }
     }
 
-    /// Returns true if exit status is zero. Signal termination is not considered a success, and success is defined as a zero exit status.
-    pub fn success(&self) -> bool {
-        self.code == Some(0)
+    /// Opens the file of the stream if it's redirected to a file.
+    fn open_file(&self) -> std::io::Result<Option<File>> {
+        match self {
+            StdioMode::File(path) => File::create(path).map(Some),
+            _ => Ok(None),
+        }
     }
 }
 
-/// The output of a finished process.
-#[derive(Debug)]
-pub struct Output {
-    /// The status (exit code) of the process.
-    pub status: ExitStatus,
-    /// The data that the process wrote to stdout.
-    pub stdout: Vec<u8>,
-    /// The data that the process wrote to stderr.
-    pub stderr: Vec<u8>,
+/// The configuration of the child process standard streams.
+#[derive(Debug, Clone)]
+struct StdioConfig {
+    stdin: StdioMode,
+    stdout: StdioMode,
+    stderr: StdioMode,
 }
 
-fn relative_command_path(command: &Path) -> crate::Result<PathBuf> {
-    match platform::current_exe()?.parent() {
-        #[cfg(windows)]
-        Some(exe_dir) => Ok(exe_dir.join(command).with_extension("exe")),
-        #[cfg(not(windows))]
-        Some(exe_dir) => Ok(exe_dir.join(command)),
-        None => Err(crate::Error::CurrentExeHasNoParent),
+impl Default for StdioConfig {
+    fn default() -> Self {
+        Self {
+            stdin: StdioMode::Piped,
+            stdout: StdioMode::Piped,
+            stderr: StdioMode::Piped,
+        }
     }
 }
 
-impl From<Command> for StdCommand {
-    fn from(cmd: Command) -> StdCommand {
-        cmd.cmd
-    }
+/// Spawned child process.
+#[derive(Debug)]
+pub struct CommandChild {
+    inner: Arc<SharedChild>,
+    stdin_writer: Option<PipeWriter>,
+    // weak so the event stream still closes once the process terminates
+    events: WeakEventSender,
+    killed: Arc<AtomicBool>,
+    /// Set when the process is killed by its cancellation token, see [`Command::spawn_with_cancel`].
+    cancelled: Arc<AtomicBool>,
+    command: Command,
+    /// The wait thread, which outlives the pipe reader tasks. Joined by [`Self::shutdown`].
+    waiter: Option<JoinHandle<()>>,
+    /// Set by the wait thread once the process exits.
+    exit: watch::Receiver<Option<ExitStatus>>,
+    /// Terminates the process tree when dropped, see [`Command::contain`].
+    #[cfg(windows)]
+    #[allow(dead_code)]
+    job: Option<windows::JobObject>,
+    /// The master side of the pseudo-terminal, see [`Command::spawn_pty`].
+    #[cfg(all(unix, feature = "pty"))]
+    pty: Option<File>,
 }
 
-impl Command {
-    pub(crate) fn new<S: AsRef<OsStr>>(program: S) -> Self {
-        let mut command = StdCommand::new(program);
-
-        command.stdout(Stdio::piped());
-        command.stdin(Stdio::piped());
-        command.stderr(Stdio::piped());
-        #[cfg(windows)]
-        command.creation_flags(CREATE_NO_WINDOW);
+impl Drop for CommandChild {
+    fn drop(&mut self) {
+        if self.command.kill_on_drop {
+            // no-op if the process already exited
+            self.killed.store(true, Ordering::Relaxed);
+            let _ = self.kill_process();
+        }
+    }
+}
 
-        Self {
-            cmd: command,
-            raw_out: false,
+impl CommandChild {
+    /// Writes to process stdin.
+    ///
+    /// Fails if the stdin of the process is not piped, e.g. when spawned with [`Command::inherit_tty`].
+    pub fn write(&mut self, buf: &[u8]) -> crate::Result<()> {
+        match &mut self.stdin_writer {
+            Some(writer) => writer.write_all(buf)?,
+            None => return Err(stdin_not_piped().into()),
         }
+        Ok(())
     }
 
-    pub(crate) fn new_sidecar<S: AsRef<Path>>(program: S) -> crate::Result<Self> {
-        Ok(Self::new(relative_command_path(program.as_ref())?))
+    /// Writes `line` followed by a `\n` to the process stdin, e.g. a command for a REPL-style program.
+    ///
+    /// The line is written with its terminator in a single write, and stdin isn't buffered,
+    /// so the process can read it right away.
+    pub fn write_line(&mut self, line: &str) -> crate::Result<()> {
+        let mut buf = Vec::with_capacity(line.len() + 1);
+        buf.extend_from_slice(line.as_bytes());
+        buf.push(NEWLINE_BYTE);
+        self.write(&buf)
     }
 
-    /// Appends an argument to the command.
-    #[must_use]
-    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
-        self.cmd.arg(arg);
-        self
+    /// Writes `s` to the process stdin, encoded with the [`Command::encoding`] of the command or UTF-8 by default,
+    /// e.g. to drive an interactive tool expecting Shift_JIS input.
+    ///
+    /// Characters that can't be represented in the encoding are written as HTML numeric character references,
+    /// and UTF-16 encodings write UTF-8, as specified by the Encoding Standard.
+    pub fn write_str(&mut self, s: &str) -> crate::Result<()> {
+        let encoding = self.command.encoding.unwrap_or(encoding_rs::UTF_8);
+        let (bytes, _, _) = encoding.encode(s);
+        self.write(&bytes)
     }
 
-    /// Appends arguments to the command.
-    #[must_use]
-    pub fn args<I, S>(mut self, args: I) -> Self
-    where
-        I: IntoIterator<Item = S>,
-        S: AsRef<OsStr>,
-    {
-        self.cmd.args(args);
-        self
+    /// Closes the process stdin, signalling the end of its input, e.g. for `sort` or `sha256sum` to produce their output.
+    ///
+    /// [`Self::write`] fails afterwards. Handles created with [`Self::try_clone_stdin`] must be dropped too
+    /// for the process to see the end of its input.
+    pub fn close_stdin(&mut self) -> crate::Result<()> {
+        self.stdin_writer.take().ok_or_else(stdin_not_piped)?;
+        Ok(())
     }
 
-    /// Clears the entire environment map for the child process.
-    #[must_use]
-    pub fn env_clear(mut self) -> Self {
-        self.cmd.env_clear();
-        self
+    /// Creates an independent handle to the process stdin.
+    ///
+    /// The process only sees the end of its input once all handles, including the one owned by this child, are dropped.
+    pub fn try_clone_stdin(&self) -> crate::Result<ChildStdin> {
+        match &self.stdin_writer {
+            Some(writer) => Ok(ChildStdin(writer.try_clone()?)),
+            None => Err(stdin_not_piped().into()),
+        }
     }
 
-    /// Inserts or updates an explicit environment variable mapping.
-    #[must_use]
-    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    /// Writes every chunk of `stream` to the process stdin, closing it once the stream ends.
+    ///
+    /// The stream is drained on a separate thread, and write errors are reported as [`CommandEvent::Error`] events.
+    /// Stdin is owned by the feeder from now on, so [`Self::write`] fails afterwards.
+    ///
+    /// This is useful to connect an async producer, e.g. a download, to a process consuming its stdin.
+    pub fn feed_stdin<S>(&mut self, stream: S) -> crate::Result<()>
     where
-        K: AsRef<OsStr>,
-        V: AsRef<OsStr>,
+        S: Stream<Item = Vec<u8>> + Send + 'static,
     {
-        self.cmd.env(key, value);
-        self
+        let mut writer = self.stdin_writer.take().ok_or_else(stdin_not_piped)?;
+        let events = self.events.clone();
+        spawn_thread(move || {
+            let mut stream = Box::pin(stream);
+            while let Some(chunk) =
+                block_on_task(std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)))
+            {
+                if let Err(e) = writer.write_all(&chunk) {
+                    if let Some(tx) = events.upgrade() {
+                        let _ = tx.send(CommandEvent::Error(e.to_string()));
+                    }
+                    break;
+                }
+            }
+        });
+        Ok(())
     }
 
-    /// Adds or updates multiple environment variable mappings.
-    #[must_use]
-    pub fn envs<I, K, V>(mut self, envs: I) -> Self
-    where
-        I: IntoIterator<Item = (K, V)>,
-        K: AsRef<OsStr>,
-        V: AsRef<OsStr>,
-    {
-        self.cmd.envs(envs);
-        self
+    /// Sends a kill signal to the child, or to its whole process group if spawned with `Command::process_group`.
+    ///
+    /// Output still buffered in the pipes is emitted before the event stream closes,
+    /// unless [`Command::drain_on_kill`] is disabled.
+    pub fn kill(self) -> crate::Result<()> {
+        self.killed.store(true, Ordering::Relaxed);
+        self.kill_process()?;
+        Ok(())
     }
 
-    /// Sets the working directory for the child process.
-    #[must_use]
-    pub fn current_dir<P: AsRef<Path>>(mut self, current_dir: P) -> Self {
-        self.cmd.current_dir(current_dir);
-        self
+    /// Kills the process along with every process it spawned, directly or not, e.g. the compilers run by a build tool.
+    ///
+    /// On Unix the descendants are found through `/proc` on Linux and `ps` elsewhere, and are killed deepest first
+    /// before the process itself. Descendants that exit or get reparented in the meantime are skipped.
+    /// On Windows this runs `taskkill /T /F`. Processes whose parent exited before this call
+    /// aren't descendants anymore and are left running, see [`Command::process_group`] to track them on Unix.
+    #[cfg(any(unix, windows))]
+    pub fn kill_tree(self) -> crate::Result<()> {
+        // once the process is reaped its pid might be reused, so its descendants can't be looked up
+        if self.inner.try_wait()?.is_none() {
+            #[cfg(unix)]
+            tree::kill_descendants(self.pid())?;
+            #[cfg(windows)]
+            tree::kill_tree(self.pid())?;
+        }
+        self.kill()
     }
 
-    /// Configures the reader to output bytes from the child process exactly as received
-    pub fn set_raw_out(mut self, raw_out: bool) -> Self {
-        self.raw_out = raw_out;
-        self
+    /// Sends the signal `sig` to the process, e.g. `libc::SIGHUP` to make a server reload its configuration.
+    ///
+    /// Unlike [`Self::kill`], this doesn't consume the child, so it can be called any number of times.
+    /// Does nothing if the process already exited, so the signal can't reach another process reusing its pid.
+    #[cfg(unix)]
+    pub fn signal(&self, sig: i32) -> crate::Result<()> {
+        self.inner.send_signal(sig)?;
+        Ok(())
     }
 
-    /// Spawns the command.
+    /// Asks the child to exit, killing it if it's still running after `grace`.
+    ///
+    /// On Unix this sends `SIGTERM`, giving the process a chance to clean up, e.g. to flush its state to disk,
+    /// and only sends `SIGKILL` once the grace period elapses. This blocks the current thread during the grace period.
+    /// On Windows there's no equivalent signal, so the process is killed right away like with [`Self::kill`].
     ///
     /// # Examples
     ///
     /// ```rust,no_run
-    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// use std::time::Duration;
+    /// use tauri_plugin_shell::ShellExt;
     /// tauri::Builder::default()
     ///   .setup(|app| {
-    ///     let handle = app.handle().clone();
-    ///     tauri::async_runtime::spawn(async move {
-    ///       let (mut rx, mut child) = handle.shell().command("cargo")
-    ///         .args(["tauri", "dev"])
-    ///         .spawn()
-    ///         .expect("Failed to spawn cargo");
-    ///
-    ///       let mut i = 0;
-    ///       while let Some(event) = rx.recv().await {
-    ///         if let CommandEvent::Stdout(line) = event {
-    ///           println!("got: {}", String::from_utf8(line).unwrap());
-    ///           i += 1;
-    ///           if i == 4 {
-    ///             child.write("message from Rust\n".as_bytes()).unwrap();
-    ///             i = 0;
-    ///           }
-    ///         }
-    ///       }
-    ///     });
+    ///     let (_rx, child) = app.shell().sidecar("database")?.spawn()?;
+    ///     // on shutdown
+    ///     child.terminate(Duration::from_secs(5))?;
     ///     Ok(())
-    /// });
+    ///   });
     /// ```
-    pub fn spawn(self) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
-        let raw = self.raw_out;
-        let mut command: StdCommand = self.into();
-        let (stdout_reader, stdout_writer) = pipe()?;
-        let (stderr_reader, stderr_writer) = pipe()?;
-        let (stdin_reader, stdin_writer) = pipe()?;
-        command.stdout(stdout_writer);
-        command.stderr(stderr_writer);
-        command.stdin(stdin_reader);
+    pub fn terminate(self, grace: Duration) -> crate::Result<()> {
+        #[cfg(unix)]
+        {
+            if self.command.process_group {
+                self.signal_group(libc::SIGTERM)?;
+            } else {
+                self.inner.send_signal(libc::SIGTERM)?;
+            }
+            if let WaitOutcome::Exited(_) = self.wait_timeout(grace)? {
+                return Ok(());
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = grace;
+        self.kill()
+    }
 
-        let shared_child = SharedChild::spawn(&mut command)?;
-        let child = Arc::new(shared_child);
-        let child_ = child.clone();
-        let guard = Arc::new(RwLock::new(()));
+    /// Kills the process, and its whole process group if spawned with [`Command::process_group`].
+    fn kill_process(&self) -> std::io::Result<()> {
+        kill_process(&self.inner, &self.command)
+    }
 
-        let (tx, rx) = channel(1);
+    /// Sends `sig` to the process group of a child spawned with [`Command::process_group`], if it's still running.
+    #[cfg(unix)]
+    fn signal_group(&self, sig: i32) -> std::io::Result<()> {
+        if self.inner.try_wait()?.is_some() {
+            return Ok(());
+        }
+        signal_group(self.pid(), sig)
+    }
 
-        spawn_pipe_reader(
-            tx.clone(),
-            guard.clone(),
-            stdout_reader,
-            CommandEvent::Stdout,
-            raw,
-        );
-        spawn_pipe_reader(
-            tx.clone(),
-            guard.clone(),
-            stderr_reader,
-            CommandEvent::Stderr,
-            raw,
-        );
+    /// Kills the child and releases every resource the crate holds for it, returning its exit status.
+    ///
+    /// The process stdin is closed, then the process is killed and waited for. This only returns once the
+    /// pipe reader tasks have finished and their pipes are closed, so no thread, task or file descriptor outlives this call.
+    /// If the process already exited, only its resources are released.
+    ///
+    /// The event receiver must be drained or dropped concurrently,
+    /// otherwise the readers can't deliver their remaining events and this call blocks.
+    pub fn shutdown(mut self) -> crate::Result<ExitStatus> {
+        drop(self.stdin_writer.take());
+        self.killed.store(true, Ordering::Relaxed);
+        self.kill_process()?;
+        let status = self.inner.wait()?;
+        if let Some(waiter) = self.waiter.take() {
+            let _ = waiter.join();
+        }
+        Ok(status.into())
+    }
 
-        spawn(move || {
-            let _ = match child_.wait() {
-                Ok(status) => {
-                    let _l = guard.write().unwrap();
-                    block_on_task(async move {
-                        tx.send(CommandEvent::Terminated(TerminatedPayload {
-                            code: status.code(),
-                            #[cfg(windows)]
-                            signal: None,
-                            #[cfg(unix)]
-                            signal: status.signal(),
-                        }))
-                        .await
-                    })
-                }
-                Err(e) => {
-                    let _l = guard.write().unwrap();
-                    block_on_task(async move { tx.send(CommandEvent::Error(e.to_string())).await })
-                }
-            };
-        });
+    /// Suspends the process until [`Self::resume`] is called, e.g. to free the CPU for the app temporarily.
+    ///
+    /// On Unix this sends `SIGSTOP`. On Windows this calls `NtSuspendProcess`, which is undocumented but stable,
+    /// and suspends each thread of the process: new threads created while suspended aren't suspended,
+    /// and every suspend call must be balanced by a resume call.
+    /// Output still buffered in the pipes is emitted, and a suspended process can still be killed.
+    /// Does nothing if the process already exited.
+    #[cfg(any(unix, windows))]
+    pub fn suspend(&self) -> crate::Result<()> {
+        #[cfg(unix)]
+        self.inner.send_signal(libc::SIGSTOP)?;
+        #[cfg(windows)]
+        if self.inner.try_wait()?.is_none() {
+            windows::suspend_process(self.pid())?;
+        }
+        Ok(())
+    }
 
-        Ok((
-            rx,
-            CommandChild {
-                inner: child,
-                stdin_writer,
-            },
-        ))
+    /// Resumes a process suspended with [`Self::suspend`].
+    ///
+    /// On Unix this sends `SIGCONT`. On Windows this calls `NtResumeProcess`.
+    /// Does nothing if the process already exited.
+    #[cfg(any(unix, windows))]
+    pub fn resume(&self) -> crate::Result<()> {
+        #[cfg(unix)]
+        self.inner.send_signal(libc::SIGCONT)?;
+        #[cfg(windows)]
+        if self.inner.try_wait()?.is_none() {
+            windows::resume_process(self.pid())?;
+        }
+        Ok(())
     }
 
-    /// Executes a command as a child process, waiting for it to finish and collecting its exit status.
-    /// Stdin, stdout and stderr are ignored.
+    /// Returns a future resolving to the exit status of the process once it exits.
+    ///
+    /// This can be called any number of times, e.g. by independent parts of the app that care about the process lifetime,
+    /// and every future resolves to the same status. Unlike the event receiver, the output is not consumed.
     ///
     /// # Examples
+    ///
     /// ```rust,no_run
     /// use tauri_plugin_shell::ShellExt;
     /// tauri::Builder::default()
     ///   .setup(|app| {
-    ///     let status = tauri::async_runtime::block_on(async move { app.shell().command("which").args(["ls"]).status().await.unwrap() });
-    ///     println!("`which` finished with status: {:?}", status.code());
+    ///     let (_rx, child) = app.shell().sidecar("server")?.spawn()?;
+    ///     let exited = child.subscribe_exit();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       if let Ok(status) = exited.await {
+    ///         println!("server exited with {:?}", status.code());
+    ///       }
+    ///     });
     ///     Ok(())
     ///   });
     /// ```
-    pub async fn status(self) -> crate::Result<ExitStatus> {
-        let (mut rx, _child) = self.spawn()?;
-        let mut code = None;
-        #[allow(clippy::collapsible_match)]
-        while let Some(event) = rx.recv().await {
-            if let CommandEvent::Terminated(payload) = event {
-                code = payload.code;
+    pub fn subscribe_exit(
+        &self,
+    ) -> impl std::future::Future<Output = crate::Result<ExitStatus>> + Send + 'static {
+        let mut exit = self.exit.clone();
+        async move {
+            match exit.wait_for(Option::is_some).await {
+                Ok(status) => Ok(status.clone().expect("checked by wait_for")),
+                // the wait thread failed to wait for the process
+                Err(_) => Err(crate::Error::Io(std::io::Error::other(
+                    "failed to wait for the process to exit",
+                ))),
             }
         }
-        Ok(ExitStatus { code })
     }
 
-    /// Executes the command as a child process, waiting for it to finish and collecting all of its output.
-    /// Stdin is ignored.
-    ///
-    /// # Examples
+    /// Waits for the process to exit, returning its exit status.
     ///
-    /// ```rust,no_run
-    /// use tauri_plugin_shell::ShellExt;
-    /// tauri::Builder::default()
-    ///   .setup(|app| {
-    ///     let output = tauri::async_runtime::block_on(async move { app.shell().command("echo").args(["TAURI"]).output().await.unwrap() });
-    ///     assert!(output.status.success());
-    ///     assert_eq!(String::from_utf8(output.stdout).unwrap(), "TAURI");
-    ///     Ok(())
-    ///   });
-    /// ```
-    pub async fn output(self) -> crate::Result<Output> {
-        let (mut rx, _child) = self.spawn()?;
+    /// The wait doesn't block a thread, it resolves once the thread already waiting on the process reports its exit,
+    /// so the [`CommandEvent::Terminated`] event is still emitted and the output doesn't need to be consumed.
+    /// See [`Self::subscribe_exit`] to wait without borrowing the child.
+    pub async fn wait(&self) -> crate::Result<ExitStatus> {
+        self.subscribe_exit().await
+    }
 
-        let mut code = None;
-        let mut stdout = Vec::new();
-        let mut stderr = Vec::new();
+    /// Returns the process pid.
+    pub fn pid(&self) -> u32 {
+        self.inner.id()
+    }
 
-        while let Some(event) = rx.recv().await {
-            match event {
-                CommandEvent::Terminated(payload) => {
-                    code = payload.code;
-                }
-                CommandEvent::Stdout(line) => {
-                    stdout.extend(line);
-                    stdout.push(NEWLINE_BYTE);
-                }
-                CommandEvent::Stderr(line) => {
-                    stderr.extend(line);
-                    stderr.push(NEWLINE_BYTE);
-                }
-                CommandEvent::Error(_) => {}
+    /// Resizes the pseudo-terminal of a process spawned with [`Command::spawn_pty`],
+    /// which notifies the process with `SIGWINCH`, e.g. when the terminal view rendering its output is resized.
+    ///
+    /// Fails if the process wasn't spawned with a pseudo-terminal.
+    #[cfg(all(unix, feature = "pty"))]
+    pub fn resize(&self, rows: u16, cols: u16) -> crate::Result<()> {
+        match &self.pty {
+            Some(master) => pty::resize(master, rows, cols)?,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "the process was not spawned with a pseudo-terminal",
+                )
+                .into())
             }
         }
-        Ok(Output {
-            status: ExitStatus { code },
-            stdout,
-            stderr,
-        })
+        Ok(())
     }
-}
 
-fn read_raw_bytes<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
-    mut reader: BufReader<PipeReader>,
-    tx: Sender<CommandEvent>,
-    wrapper: F,
-) {
-    loop {
-        let result = reader.fill_buf();
+    /// Returns the command this process was spawned from.
+    ///
+    /// This is useful to log the exact invocation or to spawn it again.
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
+    /// Spawns the command of this process again, once it has exited.
+    ///
+    /// This handle then refers to the new process, whose events are delivered to the returned receiver.
+    /// Fails with [`crate::Error::ProcessRunning`] if the process is still running.
+    pub fn restart(&mut self) -> crate::Result<Receiver<CommandEvent>> {
+        if self.inner.try_wait()?.is_none() {
+            return Err(crate::Error::ProcessRunning);
+        }
+        let (rx, child) = self.command.clone().spawn()?;
+        *self = child;
+        Ok(rx)
+    }
+
+    /// Spawns the command of this process again in the `dir` working directory, once it has exited.
+    ///
+    /// The new directory is retained for later restarts. See [`Self::restart`].
+    pub fn restart_in(&mut self, dir: PathBuf) -> crate::Result<Receiver<CommandEvent>> {
+        if self.inner.try_wait()?.is_none() {
+            return Err(crate::Error::ProcessRunning);
+        }
+        self.command.current_dir.replace(dir);
+        self.restart()
+    }
+
+    /// Returns the underlying [`SharedChild`] handle.
+    ///
+    /// This is an escape hatch for advanced integrations. The process is already waited on by the thread
+    /// that emits [`CommandEvent::Terminated`], so the caller is responsible for not interfering with it,
+    /// e.g. by taking its stdio handles.
+    pub fn shared(&self) -> Arc<SharedChild> {
+        self.inner.clone()
+    }
+
+    /// Returns the exit status of the process if it exited, or `None` if it's still running.
+    ///
+    /// This doesn't block, so it can be used to poll the state of the process, e.g. from a UI timer.
+    pub fn try_wait(&self) -> crate::Result<Option<ExitStatus>> {
+        Ok(self.inner.try_wait()?.map(Into::into))
+    }
+
+    /// Returns whether the process is still running, e.g. to disable a "stop" button once it exited.
+    ///
+    /// Like [`Self::try_wait`] this doesn't block or consume events, and it doesn't interfere with the thread
+    /// waiting on the process, so the [`CommandEvent::Terminated`] event is still emitted.
+    /// Returns `false` if the state of the process can't be queried.
+    pub fn is_alive(&self) -> bool {
+        self.inner.try_wait().is_ok_and(|status| status.is_none())
+    }
+
+    /// Waits for the process to exit, giving up after `timeout`.
+    ///
+    /// This blocks the current thread, polling the process status until it exits or the timeout elapses.
+    /// The [`CommandEvent::Terminated`] event is still emitted when the process exits.
+    pub fn wait_timeout(&self, timeout: Duration) -> crate::Result<WaitOutcome> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.inner.try_wait()? {
+                return Ok(WaitOutcome::Exited(status.into()));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(WaitOutcome::TimedOut);
+            }
+            std::thread::sleep((deadline - now).min(Duration::from_millis(10)));
+        }
+    }
+
+    /// Returns the OOM score adjustment of the process, read from `/proc/<pid>/oom_score_adj`.
+    #[cfg(target_os = "linux")]
+    pub fn oom_score_adj(&self) -> crate::Result<i32> {
+        let score = std::fs::read_to_string(format!("/proc/{}/oom_score_adj", self.pid()))?;
+        score.trim().parse().map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid oom_score_adj value {score:?}"),
+            )
+            .into()
+        })
+    }
+
+    /// Sets the OOM score adjustment of the process by writing to `/proc/<pid>/oom_score_adj`.
+    ///
+    /// The score must be in the `-1000..=1000` range. Higher values make the process a preferred victim
+    /// of the OOM killer under memory pressure, and `-1000` disables OOM killing for it entirely.
+    ///
+    /// Any process can raise the score of its own children,
+    /// but lowering it below its previous minimum requires the `CAP_SYS_RESOURCE` capability.
+    #[cfg(target_os = "linux")]
+    pub fn set_oom_score_adj(&self, score: i32) -> crate::Result<()> {
+        if !(-1000..=1000).contains(&score) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("oom_score_adj must be between -1000 and 1000, got {score}"),
+            )
+            .into());
+        }
+        std::fs::write(
+            format!("/proc/{}/oom_score_adj", self.pid()),
+            score.to_string(),
+        )?;
+        Ok(())
+    }
+}
+
+/// A handle to the stdin of a child process, see [`CommandChild::try_clone_stdin`].
+#[derive(Debug)]
+pub struct ChildStdin(PipeWriter);
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Number of running internal threads and tasks, see [`active_reader_threads`].
+#[cfg(feature = "diagnostics")]
+static ACTIVE_THREADS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Returns the number of internal threads and async tasks currently running for the spawned processes,
+/// i.e. the pipe reader tasks, the wait threads and the stdin feeders.
+///
+/// Once a child has terminated and its events were drained, its threads and tasks have exited,
+/// so tests can assert this returns to its previous value to detect leaks.
+///
+/// Requires the `diagnostics` feature.
+#[cfg(feature = "diagnostics")]
+pub fn active_reader_threads() -> usize {
+    ACTIVE_THREADS.load(Ordering::SeqCst)
+}
+
+/// Decrements [`ACTIVE_THREADS`] when dropped.
+#[cfg(feature = "diagnostics")]
+struct ActiveThread;
+
+#[cfg(feature = "diagnostics")]
+impl ActiveThread {
+    fn acquire() -> Self {
+        ACTIVE_THREADS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+#[cfg(feature = "diagnostics")]
+impl Drop for ActiveThread {
+    fn drop(&mut self) {
+        ACTIVE_THREADS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Spawns an internal thread, tracked by [`active_reader_threads`] with the `diagnostics` feature.
+fn spawn_thread<F: FnOnce() + Send + 'static>(f: F) -> JoinHandle<()> {
+    // counted before spawning so the thread is visible as soon as this returns
+    #[cfg(feature = "diagnostics")]
+    let active = ActiveThread::acquire();
+    std::thread::spawn(move || {
+        #[cfg(feature = "diagnostics")]
+        let _active = active;
+        f()
+    })
+}
+
+/// Spawns an internal task on the async runtime, tracked like [`spawn_thread`].
+fn spawn_task<F: Future<Output = ()> + Send + 'static>(f: F) -> TaskHandle<()> {
+    #[cfg(feature = "diagnostics")]
+    let f = {
+        let active = ActiveThread::acquire();
+        async move {
+            let _active = active;
+            f.await
+        }
+    };
+    tauri::async_runtime::spawn(f)
+}
+
+/// Runs a future to completion on the current thread without an async runtime, parking the thread while it's pending.
+///
+/// Used by the readers of [`Command::spawn_sync_channel`], whose reads and sends block the thread instead.
+fn block_on_thread<F: Future>(f: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(Unpark(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut f = std::pin::pin!(f);
+    loop {
+        if let Poll::Ready(output) = f.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// The running processes spawned by this crate, keyed by pid.
+type ChildRegistry = Mutex<HashMap<u32, Arc<SharedChild>>>;
+
+fn registry() -> &'static ChildRegistry {
+    static REGISTRY: OnceLock<ChildRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Returns the pids of the running processes spawned by this crate, in no particular order.
+///
+/// A process is removed once it exits, whether or not its [`CommandChild`] is still alive.
+pub fn running_pids() -> Vec<u32> {
+    registry().lock().unwrap().keys().copied().collect()
+}
+
+/// Kills every running process spawned by this crate, e.g. when the app exits,
+/// returning the result of each kill with the pid of the process.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tauri_plugin_shell::process::kill_all;
+/// tauri::Builder::default()
+///   .build(tauri::generate_context!())
+///   .expect("error while building tauri application")
+///   .run(|_app, event| {
+///     if let tauri::RunEvent::Exit = event {
+///       for (pid, result) in kill_all() {
+///         if let Err(e) = result {
+///           eprintln!("failed to kill process {pid}: {e}");
+///         }
+///       }
+///     }
+///   });
+/// ```
+pub fn kill_all() -> Vec<(u32, crate::Result<()>)> {
+    let children: Vec<_> = registry()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(pid, child)| (*pid, child.clone()))
+        .collect();
+    children
+        .into_iter()
+        .map(|(pid, child)| (pid, child.kill().map_err(Into::into)))
+        .collect()
+}
+
+/// Copies `input` to the process stdin on a separate thread, closing it afterwards.
+fn spawn_stdin_feeder(
+    mut writer: PipeWriter,
+    mut input: Box<dyn Read + Send>,
+    events: WeakEventSender,
+) {
+    spawn_thread(move || {
+        match std::io::copy(&mut input, &mut writer) {
+            // the process exited or closed its stdin without reading everything, which is up to it
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            Err(e) => {
+                if let Some(tx) = events.upgrade() {
+                    let _ = tx.send(CommandEvent::Error(e.to_string()));
+                }
+            }
+            Ok(_) => {}
+        }
+    });
+}
+
+/// The input written to the stdin of the process right after spawning, see [`Command::stdin_str`].
+#[derive(Debug, Clone)]
+enum StdinInput {
+    /// Shared so cloning the command doesn't copy it.
+    Data(Arc<[u8]>),
+    /// Encoded with the encoding of the command when it's spawned.
+    Text(Arc<str>),
+    File(PathBuf),
+}
+
+/// Kills `child`, or its whole process group if `command` was configured with [`Command::process_group`].
+fn kill_process(
+    child: &SharedChild,
+    #[allow(unused_variables)] command: &Command,
+) -> std::io::Result<()> {
+    // once the group leader exited, e.g. before a restart, its id might be reused by another group
+    #[cfg(unix)]
+    if command.process_group && child.try_wait()?.is_none() {
+        signal_group(child.id(), libc::SIGKILL)?;
+    }
+    child.kill()
+}
+
+/// Sends `sig` to the process group led by `pid`.
+#[cfg(unix)]
+fn signal_group(pid: u32, sig: i32) -> std::io::Result<()> {
+    // SAFETY: killpg only takes the process group id, which is the pid of the group leader
+    if unsafe { libc::killpg(pid as libc::pid_t, sig) } == -1 {
+        let e = std::io::Error::last_os_error();
+        // every process of the group already exited
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn stdin_not_piped() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::BrokenPipe,
+        "the process stdin is not piped or was closed",
+    )
+}
+
+/// Handle to subscribe to the events of a command spawned with [`Command::spawn_broadcast`].
+///
+/// The handle can be cloned and doesn't keep the event stream open:
+/// once the process terminated and its last event was sent, new subscribers receive no events.
+#[derive(Debug, Clone)]
+pub struct EventBroadcast {
+    sender: Weak<broadcast::Sender<CommandEvent>>,
+    /// Subscribed before spawning, so the first subscriber receives the events from the start.
+    first: Arc<Mutex<Option<broadcast::Receiver<CommandEvent>>>>,
+}
+
+impl EventBroadcast {
+    /// Subscribes to the events of the process.
+    ///
+    /// The first subscriber receives every event since the process was spawned, as long as they fit the capacity
+    /// of the channel. Later subscribers only receive the events sent after they subscribed.
+    pub fn subscribe(&self) -> broadcast::Receiver<CommandEvent> {
+        if let Some(rx) = self.first.lock().unwrap().take() {
+            return rx;
+        }
+        match self.sender.upgrade() {
+            Some(tx) => tx.subscribe(),
+            // the event stream is closed, so is the receiver of a channel whose sender is dropped
+            None => broadcast::channel(1).1,
+        }
+    }
+}
+
+/// Handle to a command spawned with [`Command::spawn_supervised`].
+#[derive(Debug)]
+pub struct Supervisor {
+    state: Arc<SupervisorState>,
+}
+
+#[derive(Debug)]
+struct SupervisorState {
+    stopped: AtomicBool,
+    child: Mutex<Option<CommandChild>>,
+}
+
+impl SupervisorState {
+    fn stop(&self) -> crate::Result<()> {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(child) = self.child.lock().unwrap().take() {
+            child.kill()?;
+        }
+        Ok(())
+    }
+}
+
+impl Supervisor {
+    /// Returns the pid of the currently running process, if any.
+    pub fn pid(&self) -> Option<u32> {
+        self.state.child.lock().unwrap().as_ref().map(|c| c.pid())
+    }
+
+    /// Kills the current process and stops restarting it.
+    pub fn stop(self) -> crate::Result<()> {
+        self.state.stop()
+    }
+}
+
+/// A long-lived process bundling its event stream, stdin and exit status, see [`Command::start`].
+///
+/// The process is killed when the session is dropped, unless it's [detached](Self::detach).
+#[derive(Debug)]
+pub struct Session {
+    events: Receiver<CommandEvent>,
+    child: ChildGuard,
+}
+
+impl Session {
+    /// Receives the next event of the process, `None` once the event stream is closed.
+    pub async fn recv(&mut self) -> Option<CommandEvent> {
+        self.events.recv().await
+    }
+
+    /// Returns the event receiver of the process.
+    pub fn events(&mut self) -> &mut Receiver<CommandEvent> {
+        &mut self.events
+    }
+
+    /// Writes to the process stdin, see [`CommandChild::write`].
+    pub fn write(&mut self, buf: &[u8]) -> crate::Result<()> {
+        self.child_mut().write(buf)
+    }
+
+    /// Creates an independent handle to the process stdin, see [`CommandChild::try_clone_stdin`].
+    pub fn stdin(&self) -> crate::Result<ChildStdin> {
+        self.child().try_clone_stdin()
+    }
+
+    /// Returns a future resolving to the exit status of the process, see [`CommandChild::subscribe_exit`].
+    pub fn exited(
+        &self,
+    ) -> impl std::future::Future<Output = crate::Result<ExitStatus>> + Send + 'static {
+        self.child().subscribe_exit()
+    }
+
+    /// Returns the process pid.
+    pub fn pid(&self) -> u32 {
+        self.child().pid()
+    }
+
+    /// Returns the underlying child.
+    pub fn child(&self) -> &CommandChild {
+        self.child
+            .0
+            .as_ref()
+            .expect("the child is only taken when consuming the session")
+    }
+
+    fn child_mut(&mut self) -> &mut CommandChild {
+        self.child
+            .0
+            .as_mut()
+            .expect("the child is only taken when consuming the session")
+    }
+
+    /// Kills the process.
+    pub fn kill(mut self) -> crate::Result<()> {
+        self.child.0.take().map_or(Ok(()), CommandChild::kill)
+    }
+
+    /// Releases the event receiver and the child, so the process keeps running once they're dropped,
+    /// unless [`Command::kill_on_drop`] is enabled.
+    pub fn detach(self) -> (Receiver<CommandEvent>, CommandChild) {
+        let Session { events, mut child } = self;
+        let child = child
+            .0
+            .take()
+            .expect("the child is only taken when consuming the session");
+        (events, child)
+    }
+}
+
+/// Exponential backoff applied between restarts of a supervised command.
+#[derive(Debug, Clone, Copy)]
+struct RestartBackoff {
+    initial: Duration,
+    max: Duration,
+    factor: f64,
+}
+
+impl Default for RestartBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+            factor: 2.0,
+        }
+    }
+}
+
+impl RestartBackoff {
+    /// The delay before the next restart after `failures` consecutive failures.
+    fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial.as_secs_f64() * self.factor.max(1.0).powi(exponent);
+        Duration::try_from_secs_f64(delay)
+            .map(|delay| delay.min(self.max))
+            .unwrap_or(self.max)
+    }
+}
+
+/// The scheduling priority of a child process, see [`Command::priority`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessPriority {
+    /// Nice value `10` on Unix, `BELOW_NORMAL_PRIORITY_CLASS` on Windows.
+    Low,
+    /// Nice value `0` on Unix, `NORMAL_PRIORITY_CLASS` on Windows.
+    Normal,
+    /// Nice value `-10` on Unix, `HIGH_PRIORITY_CLASS` on Windows.
+    ///
+    /// Raising the priority usually requires elevated privileges on Unix.
+    High,
+}
+
+impl ProcessPriority {
+    #[cfg(unix)]
+    fn nice(self) -> libc::c_int {
+        match self {
+            Self::Low => 10,
+            Self::Normal => 0,
+            Self::High => -10,
+        }
+    }
+
+    #[cfg(windows)]
+    fn priority_class(self) -> u32 {
+        match self {
+            Self::Low => BELOW_NORMAL_PRIORITY_CLASS,
+            Self::Normal => NORMAL_PRIORITY_CLASS,
+            Self::High => HIGH_PRIORITY_CLASS,
+        }
+    }
+}
+
+/// A resource of a child process limited with [`Command::rlimit`].
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// `RLIMIT_AS`, the maximum size of the virtual memory in bytes.
+    AddressSpace,
+    /// `RLIMIT_CORE`, the maximum size of a core dump in bytes.
+    Core,
+    /// `RLIMIT_CPU`, the CPU time in seconds. The process receives `SIGXCPU` when it reaches the soft limit
+    /// and is killed when it reaches the hard limit.
+    Cpu,
+    /// `RLIMIT_DATA`, the maximum size of the data segment in bytes.
+    Data,
+    /// `RLIMIT_FSIZE`, the maximum size of a file the process creates in bytes.
+    FileSize,
+    /// `RLIMIT_NOFILE`, one more than the highest file descriptor the process can open.
+    NoFile,
+    /// `RLIMIT_STACK`, the maximum size of the stack in bytes.
+    Stack,
+}
+
+#[cfg(unix)]
+impl Resource {
+    fn resource(self) -> libc::c_int {
+        (match self {
+            Self::AddressSpace => libc::RLIMIT_AS,
+            Self::Core => libc::RLIMIT_CORE,
+            Self::Cpu => libc::RLIMIT_CPU,
+            Self::Data => libc::RLIMIT_DATA,
+            Self::FileSize => libc::RLIMIT_FSIZE,
+            Self::NoFile => libc::RLIMIT_NOFILE,
+            Self::Stack => libc::RLIMIT_STACK,
+        }) as libc::c_int
+    }
+}
+
+/// Describes the result of a process after it has terminated.
+#[derive(Debug, Clone)]
+pub struct ExitStatus {
+    code: Option<i32>,
+    signal: Option<i32>,
+}
+
+impl From<std::process::ExitStatus> for ExitStatus {
+    fn from(status: std::process::ExitStatus) -> Self {
+        Self {
+            code: status.code(),
+            #[cfg(windows)]
+            signal: None,
+            #[cfg(unix)]
+            signal: status.signal(),
+        }
+    }
+}
+
+impl ExitStatus {
+    fn from_payload(payload: &TerminatedPayload) -> Self {
+        Self {
+            code: payload.code,
+            signal: payload.signal,
+        }
+    }
+
+    /// Returns the exit code of the process, if any.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// If the process was terminated by a signal, returns that signal.
+    ///
+    /// Distinguishes a crash, e.g. `SIGSEGV`, from a non-zero exit, which has a [`Self::code`] instead.
+    /// Always `None` on Windows.
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    /// Returns true if exit status is zero. Signal termination is not considered a success, and success is defined as a zero exit status.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Returns true if exit status is zero or if the process was terminated by one of the `allow_signals`.
+    ///
+    /// This is useful when a process is deliberately stopped, e.g. with `SIGTERM`,
+    /// and that shouldn't be reported as a failure.
+    pub fn success_with(&self, allow_signals: &[i32]) -> bool {
+        self.success()
+            || self
+                .signal
+                .is_some_and(|signal| allow_signals.contains(&signal))
+    }
+}
+
+/// The outcome of [`CommandChild::wait_timeout`].
+#[derive(Debug)]
+pub enum WaitOutcome {
+    /// The process exited before the timeout.
+    Exited(ExitStatus),
+    /// The timeout elapsed and the process is still running.
+    TimedOut,
+}
+
+/// The output of a finished process.
+#[derive(Debug)]
+pub struct Output {
+    /// The status (exit code) of the process.
+    pub status: ExitStatus,
+    /// The data that the process wrote to stdout.
+    pub stdout: Vec<u8>,
+    /// The data that the process wrote to stderr.
+    pub stderr: Vec<u8>,
+    /// The number of bytes collected from stdout, including the ones dropped by [`Command::keep_last_bytes`].
+    pub stdout_total: usize,
+    /// The number of bytes collected from stderr, including the ones dropped by [`Command::keep_last_bytes`].
+    pub stderr_total: usize,
+}
+
+/// The context needed to reproduce how a command is spawned, see [`Command::diagnostics`].
+///
+/// Serializable to JSON so it can be attached to bug reports.
+/// The environment variables may contain secrets and should be redacted before sharing.
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandDiagnostics {
+    /// The executable the program resolves to, `None` if it can't be found.
+    pub resolved_program: Option<PathBuf>,
+    /// The program followed by its arguments, exactly as they are passed to the process.
+    pub argv: Vec<String>,
+    /// The variables set or changed compared to the environment of the current process.
+    pub env_set: BTreeMap<String, String>,
+    /// The variables of the current process that are not passed to the child.
+    pub env_removed: Vec<String>,
+    /// The working directory of the child process.
+    pub current_dir: Option<PathBuf>,
+    /// The operating system, e.g. `linux` or `windows`.
+    pub os: &'static str,
+    /// The CPU architecture, e.g. `x86_64` or `aarch64`.
+    pub arch: &'static str,
+}
+
+/// The output of a finished process captured with [`Command::dual_capture`].
+#[derive(Debug)]
+pub struct DualOutput {
+    /// The status (exit code) of the process.
+    pub status: ExitStatus,
+    /// The data that the process wrote to stdout.
+    pub stdout: Vec<u8>,
+    /// The data that the process wrote to stderr.
+    pub stderr: Vec<u8>,
+    /// The data that the process wrote to both stdout and stderr, in the order it was received.
+    ///
+    /// Stdout and stderr are read by separate tasks, so the interleaving is best-effort
+    /// and might not match the order a terminal would display the output in.
+    pub merged: Vec<u8>,
+}
+
+/// A command that captures its separated and merged output in a single run.
+///
+/// See [`Command::dual_capture`].
+#[derive(Debug)]
+pub struct DualCapture {
+    command: Command,
+}
+
+impl DualCapture {
+    /// Executes the command as a child process, waiting for it to finish and collecting all of its output.
+    /// Stdin is ignored. Dropping the returned future before it completes kills the process.
+    pub async fn output(self) -> crate::Result<DualOutput> {
+        let (mut rx, child) = self.command.spawn()?;
+        let _guard = ChildGuard(Some(child));
+
+        let mut status = ExitStatus {
+            code: None,
+            signal: None,
+        };
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let mut merged = Vec::new();
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Terminated(payload) => {
+                    status = ExitStatus::from_payload(&payload);
+                }
+                // the events keep their line endings, so the output is reassembled as written
+                CommandEvent::Stdout(line) => {
+                    merged.extend(&line);
+                    stdout.extend(line);
+                }
+                CommandEvent::Stderr(line) => {
+                    merged.extend(&line);
+                    stderr.extend(line);
+                }
+                CommandEvent::StdoutBatch(lines) => {
+                    for line in lines {
+                        merged.extend(&line);
+                        stdout.extend(line);
+                    }
+                }
+                CommandEvent::StderrBatch(lines) => {
+                    for line in lines {
+                        merged.extend(&line);
+                        stderr.extend(line);
+                    }
+                }
+                CommandEvent::Error(_) | CommandEvent::FirstOutput { .. } => {}
+            }
+        }
+        Ok(DualOutput {
+            status,
+            stdout,
+            stderr,
+            merged,
+        })
+    }
+}
+
+/// A command spawned as a detached process that outlives the app.
+///
+/// See [`Command::detached`].
+#[derive(Debug)]
+pub struct DetachedCommand {
+    command: Command,
+}
+
+impl DetachedCommand {
+    /// Spawns the command as a detached process, returning its pid.
+    ///
+    /// Stdin, stdout and stderr are redirected to the null device, so no events are emitted.
+    /// The process isn't tracked by [`kill_all`], and [`Command::kill_on_drop`] doesn't apply to it.
+    pub fn spawn(mut self) -> crate::Result<u32> {
+        #[cfg(windows)]
+        let creation_flags = self.command.creation_flags;
+        // the new session is also a new process group, and `setsid` fails if the process already leads one
+        #[cfg(unix)]
+        {
+            self.command.process_group = false;
+        }
+        let mut command: StdCommand = self.command.into();
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: setsid is async-signal-safe and doesn't allocate
+            unsafe {
+                command.pre_exec(|| {
+                    if libc::setsid() == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+        #[cfg(windows)]
+        command.creation_flags(creation_flags | DETACHED_PROCESS);
+
+        let mut child = command.spawn()?;
+        let pid = child.id();
+        // reap the process if it exits while the app is running
+        spawn_thread(move || {
+            let _ = child.wait();
+        });
+        Ok(pid)
+    }
+}
+
+/// Returns the path of the sidecar `command` next to the current executable.
+///
+/// When it doesn't exist, the variant suffixed with the target triple is used if it does,
+/// e.g. `my-binary-x86_64-unknown-linux-gnu`, since the suffix is only stripped when bundling.
+fn relative_command_path(command: &Path) -> crate::Result<PathBuf> {
+    let current_exe = platform::current_exe()?;
+    let exe_dir = current_exe
+        .parent()
+        .ok_or(crate::Error::CurrentExeHasNoParent)?;
+    let path = exe_dir.join(command);
+    let plain = executable_path(&path);
+    if plain.exists() {
+        return Ok(plain);
+    }
+    let suffixed = platform::target_triple().ok().and_then(|triple| {
+        let mut file_name = path.file_name()?.to_os_string();
+        file_name.push("-");
+        file_name.push(triple);
+        Some(executable_path(&path.with_file_name(file_name)))
+    });
+    Ok(suffixed.filter(|path| path.exists()).unwrap_or(plain))
+}
+
+/// Returns the path of the executable file of the program at `path`.
+fn executable_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        path.with_extension("exe")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Spawns `command`, the process built from `source`.
+fn spawn_child(
+    source: &Command,
+    command: &mut StdCommand,
+) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+    let (tx, rx) = channel(source.event_buffer);
+    let child = spawn_child_with_sender(source, command, EventSender::Async(tx))?;
+    Ok((rx, child))
+}
+
+/// Spawns `command`, the process built from `source`, delivering its events to `tx`.
+fn spawn_child_with_sender(
+    source: &Command,
+    command: &mut StdCommand,
+    tx: EventSender,
+) -> crate::Result<CommandChild> {
+    let mut options = source.reader_options();
+    // the sync channel is drained without an async runtime, so the readers don't use it either
+    options.blocking = matches!(tx, EventSender::Sync(_));
+    let stdio = &source.stdio;
+    if options.measure_first_output {
+        options.first_output.replace(Arc::new(FirstOutput {
+            spawned_at: Instant::now(),
+            reported: AtomicBool::new(false),
+        }));
+    }
+    let killed = Arc::new(AtomicBool::new(false));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    if !options.drain_on_kill {
+        options.killed.replace(killed.clone());
+    }
+    #[cfg(all(unix, feature = "pty"))]
+    let pty = source
+        .pty
+        .map(|(rows, cols)| pty::Pty::open(rows, cols))
+        .transpose()?;
+    // the pseudo-terminal replaces the pipes
+    #[cfg(all(unix, feature = "pty"))]
+    let use_pipes = pty.is_none();
+    #[cfg(not(all(unix, feature = "pty")))]
+    let use_pipes = true;
+    let piped = |mode: &StdioMode| match mode {
+        StdioMode::Piped if use_pipes => pipe().map(Some),
+        _ => Ok(None),
+    };
+    let stdout_pipe = piped(&stdio.stdout)?;
+    let stderr_pipe = piped(&stdio.stderr)?;
+    let stdin_pipe = piped(&stdio.stdin)?;
+    let stdout_file = stdio.stdout.open_file()?;
+    let stderr_file = stdio.stderr.open_file()?;
+
+    let stdin_input: Option<Box<dyn Read + Send>> = match &source.stdin_input {
+        Some(StdinInput::Data(data)) => Some(Box::new(std::io::Cursor::new(data.clone()))),
+        Some(StdinInput::Text(text)) => match source.encoding {
+            Some(encoding) if encoding != encoding_rs::UTF_8 => Some(Box::new(
+                std::io::Cursor::new(encoding.encode(text).0.into_owned()),
+            )),
+            _ => Some(Box::new(std::io::Cursor::new(Arc::<[u8]>::from(
+                text.clone(),
+            )))),
+        },
+        Some(StdinInput::File(path)) => Some(Box::new(File::open(path)?)),
+        None => None,
+    };
+
+    // the command of the current fallback program, see `Command::or_program`
+    let mut fallback: Option<StdCommand> = None;
+    let mut fallback_programs = source.fallback_programs.iter();
+    let mut attempted = Vec::new();
+    let shared_child = loop {
+        let command = fallback.as_mut().unwrap_or(&mut *command);
+        let stdout = || -> std::io::Result<Stdio> {
+            Ok(match (&stdout_pipe, &stdout_file) {
+                (Some((_, writer)), _) => Stdio::from(writer.try_clone()?),
+                (_, Some(file)) => Stdio::from(file.try_clone()?),
+                _ => stdio.stdout.unpiped(),
+            })
+        };
+        command.stderr(match (&stderr_pipe, &stderr_file) {
+            _ if stdio.stderr == StdioMode::Stdout => stdout()?,
+            (Some((_, writer)), _) => Stdio::from(writer.try_clone()?),
+            (_, Some(file)) => Stdio::from(file.try_clone()?),
+            _ => stdio.stderr.unpiped(),
+        });
+        command.stdout(stdout()?);
+        command.stdin(match &stdin_pipe {
+            Some((reader, _)) => Stdio::from(reader.try_clone()?),
+            None => stdio.stdin.unpiped(),
+        });
+        #[cfg(all(unix, feature = "pty"))]
+        if let Some(pty) = &pty {
+            command.stdin(Stdio::from(pty.slave.try_clone()?));
+            command.stdout(Stdio::from(pty.slave.try_clone()?));
+            command.stderr(Stdio::from(pty.slave.try_clone()?));
+        }
+        // resumed once it's assigned to the job
+        #[cfg(windows)]
+        if source.contain {
+            command.creation_flags(creation_flags(source) | CREATE_SUSPENDED);
+        }
+        let result = SharedChild::spawn(command);
+        // release our handles to the child's end of the pipes so the readers see EOF when it exits
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+        command.stdin(Stdio::null());
+
+        match result {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let program = command.get_program().to_os_string();
+                attempted.extend(
+                    source
+                        .resolve_dirs
+                        .iter()
+                        .map(|dir| dir.join(&program).into_os_string()),
+                );
+                attempted.push(program);
+                match fallback_programs.next() {
+                    Some(program) => {
+                        fallback.replace(
+                            Command {
+                                program: program.clone(),
+                                ..source.clone()
+                            }
+                            .into(),
+                        );
+                    }
+                    None if attempted.len() > 1 => {
+                        return Err(crate::Error::ProgramNotFound(attempted))
+                    }
+                    None => return Err(e.into()),
+                }
+            }
+            result => break result?,
+        }
+    };
+    let spawned_at = Instant::now();
+    #[cfg(windows)]
+    let job = if source.contain {
+        match windows::contain_process(shared_child.id()) {
+            Ok(job) => Some(job),
+            Err(e) => {
+                let _ = shared_child.kill();
+                return Err(e.into());
+            }
+        }
+    } else {
+        None
+    };
+    let command = fallback.as_ref().unwrap_or(command);
+    #[cfg(target_os = "linux")]
+    let (program, cwd) = (
+        PathBuf::from(command.get_program()),
+        command.get_current_dir().map(Path::to_path_buf),
+    );
+    let stdout_reader = stdout_pipe.map(|(reader, _)| reader);
+    let stderr_reader = stderr_pipe.map(|(reader, _)| reader);
+    let stdin_writer = stdin_pipe.map(|(_, writer)| writer);
+    // the slave is dropped so reading the master fails once the process and its descendants close it
+    #[cfg(all(unix, feature = "pty"))]
+    let pty_master = pty.map(|pty| pty.master);
+    #[cfg(all(unix, feature = "pty"))]
+    let stdin_writer = match &pty_master {
+        Some(master) => Some(PipeWriter::from(std::os::fd::OwnedFd::from(
+            master.try_clone()?,
+        ))),
+        None => stdin_writer,
+    };
+
+    let child = Arc::new(shared_child);
+    let child_ = child.clone();
+    let cancelled_ = cancelled.clone();
+    registry().lock().unwrap().insert(child.id(), child.clone());
+
+    let tx_weak = tx.downgrade();
+    let mut readers = Vec::with_capacity(2);
+    let mut dispatcher = None;
+    let (exit_tx, exit_rx) = watch::channel(None);
+
+    let stdin_writer = match (stdin_input, stdin_writer) {
+        (Some(input), Some(writer)) => {
+            spawn_stdin_feeder(writer, input, tx_weak.clone());
+            None
+        }
+        (_, writer) => writer,
+    };
+
+    let (stdout_tx, stderr_tx) = match (source.stream_priority, &stdout_reader, &stderr_reader) {
+        (Some(priority), Some(_), Some(_)) => {
+            let (queue, thread) = Dispatcher::spawn(tx.clone());
+            // joined after the readers, so the queued events are delivered before the termination
+            dispatcher.replace(thread);
+            (
+                EventSender::Dispatch(Arc::new(queue.sender(priority == Priority::Stdout))),
+                EventSender::Dispatch(Arc::new(queue.sender(priority == Priority::Stderr))),
+            )
+        }
+        _ => (tx.clone(), tx.clone()),
+    };
+
+    #[cfg(all(unix, feature = "pty"))]
+    if let Some(master) = &pty_master {
+        readers.push(spawn_pipe_reader(
+            tx.clone(),
+            PipeSource::Pty(master.try_clone()?),
+            CommandEvent::Stdout,
+            ReaderOptions {
+                tee: source.tee_stdout.clone(),
+                ..options.clone()
+            },
+        ));
+    }
+    if let Some(stdout_reader) = stdout_reader {
+        readers.push(spawn_pipe_reader(
+            stdout_tx,
+            PipeSource::Pipe(stdout_reader),
+            CommandEvent::Stdout,
+            ReaderOptions {
+                tee: source.tee_stdout.clone(),
+                ..options.clone()
+            },
+        ));
+    }
+    if let Some(stderr_reader) = stderr_reader {
+        readers.push(spawn_pipe_reader(
+            stderr_tx,
+            PipeSource::Pipe(stderr_reader),
+            CommandEvent::Stderr,
+            ReaderOptions {
+                tee: source.tee_stderr.clone(),
+                ..options
+            },
+        ));
+    }
+
+    let waiter = spawn_thread(move || {
+        let status = child_.wait();
+        let duration_ms = spawned_at.elapsed().as_millis() as u64;
+        {
+            let mut registry = registry().lock().unwrap();
+            // the pid might already be reused by a process spawned after this one exited
+            if registry
+                .get(&child_.id())
+                .is_some_and(|child| Arc::ptr_eq(child, &child_))
+            {
+                registry.remove(&child_.id());
+            }
+        }
+        if let Ok(status) = &status {
+            exit_tx.send_replace(Some((*status).into()));
+        }
+        // emit the termination after all of the output; the readers finish on their own
+        // once the pipes are closed or the receiver is dropped, so this can't deadlock
+        for reader in readers {
+            reader.join();
+        }
+        if let Some(dispatcher) = dispatcher {
+            let _ = dispatcher.join();
+        }
+        let _ = match status {
+            Ok(_) if cancelled_.load(Ordering::Relaxed) => {
+                tx.send(CommandEvent::Terminated(TerminatedPayload {
+                    code: None,
+                    signal: Some(CANCELLED_SIGNAL),
+                    core_path: None,
+                    duration_ms,
+                }))
+            }
+            Ok(status) => tx.send(CommandEvent::Terminated(TerminatedPayload {
+                code: status.code(),
+                #[cfg(windows)]
+                signal: None,
+                #[cfg(unix)]
+                signal: status.signal(),
+                #[cfg(target_os = "linux")]
+                core_path: if status.core_dumped() {
+                    core_dump_path(child_.id(), &program, cwd.as_deref())
+                } else {
+                    None
+                },
+                #[cfg(not(target_os = "linux"))]
+                core_path: None,
+                duration_ms,
+            })),
+            Err(e) => tx.send(CommandEvent::Error(e.to_string())),
+        };
+    });
+
+    Ok(CommandChild {
+        inner: child,
+        stdin_writer,
+        events: tx_weak,
+        killed,
+        cancelled,
+        command: source.clone(),
+        waiter: Some(waiter),
+        exit: exit_rx,
+        #[cfg(windows)]
+        job,
+        #[cfg(all(unix, feature = "pty"))]
+        pty: pty_master,
+    })
+}
+
+/// The sending half of the event channel of a spawned process.
+#[derive(Debug, Clone)]
+enum EventSender {
+    Async(Sender<CommandEvent>),
+    // shared so the child can hold a weak handle, like the async sender
+    Sync(Arc<mpsc::SyncSender<CommandEvent>>),
+    /// A reader queuing its events to a [`Dispatcher`], see [`Command::stream_priority`].
+    Dispatch(Arc<DispatchSender>),
+    /// Stamps each event with the time it is sent, see [`Command::spawn_timestamped`].
+    Timestamped(Sender<(Instant, CommandEvent)>),
+    // shared so the child and the subscribe handle can hold a weak handle, like the async sender
+    Broadcast(Arc<broadcast::Sender<CommandEvent>>),
+}
+
+/// A handle to an [`EventSender`] that doesn't keep the event channel open.
+#[derive(Debug, Clone)]
+enum WeakEventSender {
+    Async(WeakSender<CommandEvent>),
+    Sync(Weak<mpsc::SyncSender<CommandEvent>>),
+    Dispatch(Weak<DispatchSender>),
+    Timestamped(WeakSender<(Instant, CommandEvent)>),
+    Broadcast(Weak<broadcast::Sender<CommandEvent>>),
+}
+
+impl EventSender {
+    /// Sends the event, waiting for capacity. Fails if the receiver was dropped.
+    ///
+    /// Only called from the internal threads, which are not part of the async runtime, see [`Self::send_async`].
+    fn send(&self, event: CommandEvent) -> Result<(), CommandEvent> {
+        match self {
+            Self::Async(tx) => tx.blocking_send(event).map_err(|e| e.0),
+            Self::Sync(tx) => tx.send(event).map_err(|e| e.0),
+            Self::Dispatch(tx) => block_on_thread(tx.send(event)),
+            Self::Timestamped(tx) => {
+                // stamped before waiting for capacity, so the time is when the event was produced
+                let event = (Instant::now(), event);
+                tx.blocking_send(event).map_err(|e| (e.0).1)
+            }
+            // without subscribers the event is dropped, the output keeps being read so the process doesn't block
+            Self::Broadcast(tx) => {
+                let _ = tx.send(event);
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends the event from an async task, waiting for capacity without blocking the runtime.
+    /// Fails if the receiver was dropped.
+    async fn send_async(&self, event: CommandEvent) -> Result<(), CommandEvent> {
+        match self {
+            Self::Async(tx) => tx.send(event).await.map_err(|e| e.0),
+            // only used by the readers running on their own threads, see `ReaderOptions::blocking`
+            Self::Sync(tx) => tx.send(event).map_err(|e| e.0),
+            Self::Dispatch(tx) => tx.send(event).await,
+            Self::Timestamped(tx) => {
+                let event = (Instant::now(), event);
+                tx.send(event).await.map_err(|e| (e.0).1)
+            }
+            Self::Broadcast(_) => self.send(event),
+        }
+    }
+
+    fn downgrade(&self) -> WeakEventSender {
+        match self {
+            Self::Async(tx) => WeakEventSender::Async(tx.downgrade()),
+            Self::Sync(tx) => WeakEventSender::Sync(Arc::downgrade(tx)),
+            Self::Dispatch(tx) => WeakEventSender::Dispatch(Arc::downgrade(tx)),
+            Self::Timestamped(tx) => WeakEventSender::Timestamped(tx.downgrade()),
+            Self::Broadcast(tx) => WeakEventSender::Broadcast(Arc::downgrade(tx)),
+        }
+    }
+}
+
+impl WeakEventSender {
+    fn upgrade(&self) -> Option<EventSender> {
+        match self {
+            Self::Async(tx) => tx.upgrade().map(EventSender::Async),
+            Self::Sync(tx) => tx.upgrade().map(EventSender::Sync),
+            Self::Dispatch(tx) => tx.upgrade().map(EventSender::Dispatch),
+            Self::Timestamped(tx) => tx.upgrade().map(EventSender::Timestamped),
+            Self::Broadcast(tx) => tx.upgrade().map(EventSender::Broadcast),
+        }
+    }
+}
+
+/// Waits for the next command event, giving up after `timeout`.
+///
+/// Returns `Ok(None)` if no event was received before the timeout,
+/// and [`crate::Error::EventStreamClosed`] if the stream ended, which happens after the last event
+/// (usually [`CommandEvent::Terminated`]) was received.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use tauri_plugin_shell::{process::recv_timeout, ShellExt};
+/// tauri::Builder::default()
+///   .setup(|app| {
+///     let handle = app.handle().clone();
+///     tauri::async_runtime::spawn(async move {
+///       let (mut rx, mut child) = handle.shell().command("python").spawn().unwrap();
+///       child.write(b"print('ping')\n").unwrap();
+///       match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+///         Ok(Some(event)) => println!("reply: {event:?}"),
+///         Ok(None) => println!("no reply after 5 seconds"),
+///         Err(e) => println!("stream ended: {e}"),
+///       }
+///     });
+///     Ok(())
+///   });
+/// ```
+pub async fn recv_timeout(
+    rx: &mut Receiver<CommandEvent>,
+    timeout: Duration,
+) -> crate::Result<Option<CommandEvent>> {
+    match tokio::time::timeout(timeout, rx.recv()).await {
+        Ok(Some(event)) => Ok(Some(event)),
+        Ok(None) => Err(crate::Error::EventStreamClosed),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Resolves the path of the core dump of the given process from the kernel `core_pattern`.
+#[cfg(target_os = "linux")]
+fn core_dump_path(pid: u32, program: &Path, cwd: Option<&Path>) -> Option<PathBuf> {
+    let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    let pattern = pattern.trim_end();
+    // the core dump is piped to a helper program instead of being written to a file
+    if pattern.is_empty() || pattern.starts_with('|') {
+        return None;
+    }
+
+    let pid = pid.to_string();
+    let mut path = String::new();
+    let mut has_pid = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => path.push('%'),
+            Some('p') | Some('P') => {
+                path.push_str(&pid);
+                has_pid = true;
+            }
+            // the kernel truncates the executable name to 15 bytes (TASK_COMM_LEN)
+            Some('e') => path.extend(program.file_name()?.to_string_lossy().chars().take(15)),
+            Some('E') => path.push_str(&program.to_string_lossy().replace('/', "!")),
+            // other specifiers (time, hostname, uid...) can't be reliably reproduced
+            _ => return None,
+        }
+    }
+
+    let uses_pid = std::fs::read_to_string("/proc/sys/kernel/core_uses_pid")
+        .map(|v| v.trim() == "1")
+        .unwrap_or(false);
+    if uses_pid && !has_pid {
+        path.push('.');
+        path.push_str(&pid);
+    }
+
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        match cwd {
+            Some(cwd) => Some(cwd.join(path)),
+            None => std::env::current_dir().ok().map(|cwd| cwd.join(path)),
+        }
+    }
+}
+
+/// Finds the executable that runs when spawning `program`, looking it up in the `PATH` directories
+/// when it is a bare program name. On Windows, the extensions listed in `PATHEXT` are tried as well.
+///
+/// Returns `None` if the program can't be found, which is useful to check if an optional tool is installed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tauri_plugin_shell::process::which;
+/// if let Some(path) = which("ffmpeg") {
+///   println!("ffmpeg found at {}", path.display());
+/// }
+/// ```
+pub fn which(program: &str) -> Option<PathBuf> {
+    resolve_program(OsStr::new(program), std::env::var_os("PATH").as_deref())
+}
+
+/// Finds the executable that runs for `program`, looking it up in the `path` directories
+/// (`PATH` syntax) when it is a bare program name.
+fn resolve_program(program: &OsStr, path: Option<&OsStr>) -> Option<PathBuf> {
+    let program = Path::new(program);
+    if program.components().count() > 1 {
+        return find_executable(program.to_path_buf());
+    }
+    std::env::split_paths(path?).find_map(|dir| find_executable(dir.join(program)))
+}
+
+#[cfg(unix)]
+fn find_executable(path: PathBuf) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(&path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+        .then_some(path)
+}
+
+#[cfg(windows)]
+fn find_executable(path: PathBuf) -> Option<PathBuf> {
+    if path.extension().is_some() {
+        return path.is_file().then_some(path);
+    }
+    let extensions = std::env::var_os("PATHEXT")
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".into());
+    extensions
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| path.with_extension(ext.trim_start_matches('.')))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn find_executable(path: PathBuf) -> Option<PathBuf> {
+    path.is_file().then_some(path)
+}
+
+/// The environment of the child process: the inherited one (unless cleared) with the explicit changes applied.
+fn effective_env(command: &StdCommand, cleared: bool) -> HashMap<OsString, OsString> {
+    let mut env: HashMap<OsString, OsString> = if cleared {
+        HashMap::new()
+    } else {
+        std::env::vars_os().collect()
+    };
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => env.insert(key.to_os_string(), value.to_os_string()),
+            None => env.remove(key),
+        };
+    }
+    env
+}
+
+/// Returns the `PATH` variable of `env`, whose name is case insensitive on Windows.
+fn env_path(env: &HashMap<OsString, OsString>) -> Option<&OsStr> {
+    env.iter()
+        .find(|(key, _)| *key == "PATH" || (cfg!(windows) && key.eq_ignore_ascii_case("PATH")))
+        .map(|(_, value)| value.as_os_str())
+}
+
+/// Returns the process creation flags of the command, see [`Command::creation_flags`].
+#[cfg(windows)]
+fn creation_flags(cmd: &Command) -> u32 {
+    let flags = cmd.creation_flags
+        | cmd
+            .priority
+            .map(ProcessPriority::priority_class)
+            .unwrap_or_default();
+    if cmd.show_window {
+        flags
+    } else {
+        flags | CREATE_NO_WINDOW
+    }
+}
+
+/// Returns the path of `stdbuf` if the command should be wrapped with it, see [`Command::stdbuf_line`].
+#[cfg(unix)]
+fn stdbuf_program(cmd: &Command) -> Option<PathBuf> {
+    if !cmd.stdbuf_line {
+        return None;
+    }
+    if cmd.arg0.is_some() {
+        log::warn!(
+            "not forcing line buffering of {:?}, stdbuf can't forward a custom argv[0]",
+            cmd.program
+        );
+        return None;
+    }
+    let stdbuf = which("stdbuf");
+    if stdbuf.is_none() {
+        log::warn!(
+            "stdbuf not found, the output of {:?} may be delayed by buffering",
+            cmd.program
+        );
+    }
+    stdbuf
+}
+
+/// Runs `command` through `stdbuf`, keeping its arguments, environment and working directory.
+#[cfg(unix)]
+fn wrap_with_stdbuf(stdbuf: &Path, command: &StdCommand, env_cleared: bool) -> StdCommand {
+    let mut wrapped = StdCommand::new(stdbuf);
+    wrapped
+        .args(["-oL", "-eL"])
+        .arg(command.get_program())
+        .args(command.get_args());
+    if env_cleared {
+        wrapped.env_clear();
+    }
+    for (key, value) in command.get_envs() {
+        match value {
+            Some(value) => wrapped.env(key, value),
+            None => wrapped.env_remove(key),
+        };
+    }
+    if let Some(current_dir) = command.get_current_dir() {
+        wrapped.current_dir(current_dir);
+    }
+    wrapped
+}
+
+/// Whether the child process doesn't inherit the environment, which is set explicitly with an [`Command::env_fn`] hook.
+fn env_cleared(cmd: &Command) -> bool {
+    cmd.env_cleared || cmd.env_fn.is_some()
+}
+
+/// Returns the path of the program in the first of the [`Command::resolve_in`] directories containing it.
+fn resolve_in_dirs(cmd: &Command) -> Option<PathBuf> {
+    let program = Path::new(&cmd.program);
+    if program.components().count() > 1 {
+        return None;
+    }
+    cmd.resolve_dirs
+        .iter()
+        .find_map(|dir| find_executable(dir.join(program)))
+}
+
+impl From<Command> for StdCommand {
+    fn from(cmd: Command) -> StdCommand {
+        let program = resolve_in_dirs(&cmd).map_or_else(|| cmd.program.clone(), Into::into);
+        let mut command = StdCommand::new(&program);
+        #[cfg(windows)]
+        if cmd.raw_args {
+            // cmd parses its command line itself and doesn't understand the escaping of std
+            for arg in &cmd.args {
+                command.raw_arg(arg);
+            }
+        } else {
+            command.args(&cmd.args);
+        }
+        #[cfg(not(windows))]
+        command.args(&cmd.args);
+        if cmd.env_cleared {
+            command.env_clear();
+        }
+        for (key, value) in &cmd.envs {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        if let Some(current_dir) = &cmd.current_dir {
+            command.current_dir(current_dir);
+        }
+
+        if let Some(EnvHook(env_fn)) = &cmd.env_fn {
+            let env = effective_env(&command, cmd.env_cleared);
+            let program = resolve_program(command.get_program(), env_path(&env))
+                .unwrap_or_else(|| PathBuf::from(command.get_program()));
+
+            // variables that are not valid UTF-8 can't be exposed to the hook, so they're passed through untouched
+            let mut utf8_env = HashMap::new();
+            let mut other_env = Vec::new();
+            for (key, value) in env {
+                match (key.to_str(), value.to_str()) {
+                    (Some(key), Some(value)) => {
+                        utf8_env.insert(key.to_string(), value.to_string());
+                    }
+                    _ => other_env.push((key, value)),
+                }
+            }
+
+            env_fn(&program.to_string_lossy(), &mut utf8_env);
+
+            command.env_clear();
+            command.envs(other_env);
+            command.envs(utf8_env);
+        }
+
+        #[cfg(unix)]
+        let stdbuf = stdbuf_program(&cmd).filter(|_| {
+            // only wrapped if the program exists, so spawning it still fails otherwise and the fallbacks are tried
+            let env = effective_env(&command, env_cleared(&cmd));
+            resolve_program(&program, env_path(&env)).is_some()
+        });
+        #[cfg(unix)]
+        match stdbuf {
+            Some(stdbuf) => command = wrap_with_stdbuf(&stdbuf, &command, env_cleared(&cmd)),
+            None => {
+                if let Some(arg0) = &cmd.arg0 {
+                    use std::os::unix::process::CommandExt;
+                    command.arg0(arg0);
+                }
+            }
+        }
+
+        command.stdout(Stdio::piped());
+        command.stdin(Stdio::piped());
+        command.stderr(Stdio::piped());
+        #[cfg(windows)]
+        command.creation_flags(creation_flags(&cmd));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            if let Some(gid) = cmd.gid {
+                command.gid(gid);
+            }
+            if let Some(uid) = cmd.uid {
+                command.uid(uid);
+            }
+            // the session of a pseudo-terminal is also a new process group, see `pty::set_controlling_terminal`
+            #[cfg(feature = "pty")]
+            let process_group = cmd.process_group && cmd.pty.is_none();
+            #[cfg(not(feature = "pty"))]
+            let process_group = cmd.process_group;
+            if process_group {
+                command.process_group(0);
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(priority) = cmd.priority {
+            use std::os::unix::process::CommandExt;
+            let nice = priority.nice();
+            // SAFETY: setpriority is async-signal-safe and doesn't allocate
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        #[cfg(unix)]
+        if !cmd.rlimits.is_empty() {
+            use std::os::unix::process::CommandExt;
+            let rlimits = cmd.rlimits.clone();
+            // SAFETY: setrlimit is async-signal-safe and the limits are only read in the child
+            unsafe {
+                command.pre_exec(move || {
+                    for (resource, soft, hard) in &rlimits {
+                        let limit = libc::rlimit {
+                            rlim_cur: *soft as libc::rlim_t,
+                            rlim_max: *hard as libc::rlim_t,
+                        };
+                        if libc::setrlimit(resource.resource() as _, &limit) == -1 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                    Ok(())
+                });
+            }
+        }
+
+        #[cfg(all(unix, feature = "pty"))]
+        if cmd.pty.is_some() {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: only calls async-signal-safe functions
+            unsafe {
+                command.pre_exec(pty::set_controlling_terminal);
+            }
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = cmd.umask {
+            use std::os::unix::process::CommandExt;
+            // SAFETY: umask is async-signal-safe and doesn't allocate
+            unsafe {
+                command.pre_exec(move || {
+                    libc::umask(mode as libc::mode_t);
+                    Ok(())
+                });
+            }
+        }
+
+        #[cfg(all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ))]
+        if let Some(filter) = cmd.seccomp {
+            use std::os::unix::process::CommandExt;
+            // installed last so the other pre_exec hooks are not restricted by the filter
+            // SAFETY: the installer only calls async-signal-safe functions
+            unsafe {
+                command.pre_exec(filter.installer());
+            }
+        }
+        command
+    }
+}
+
+impl Command {
+    pub(crate) fn new<S: AsRef<OsStr>>(program: S) -> Self {
+        Self {
+            program: program.as_ref().to_os_string(),
+            args: Vec::new(),
+            #[cfg(unix)]
+            arg0: None,
+            envs: Vec::new(),
+            current_dir: None,
+            #[cfg(unix)]
+            umask: None,
+            #[cfg(unix)]
+            stdbuf_line: false,
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            process_group: false,
+            #[cfg(unix)]
+            rlimits: Vec::new(),
+            #[cfg(all(unix, feature = "pty"))]
+            pty: None,
+            #[cfg(windows)]
+            show_window: false,
+            #[cfg(windows)]
+            creation_flags: 0,
+            #[cfg(windows)]
+            contain: false,
+            #[cfg(windows)]
+            raw_args: false,
+            priority: None,
+            raw_out: false,
+            raw_chunk_size: DEFAULT_RAW_CHUNK_SIZE,
+            max_line_len: None,
+            encoding: None,
+            line_prefix: None,
+            delimiter: None,
+            normalize_newlines: false,
+            restart_backoff: None,
+            measure_first_output: false,
+            drain_on_kill: true,
+            kill_on_drop: false,
+            event_buffer: 1,
+            max_events_per_sec: None,
+            stream_priority: None,
+            tee_stdout: None,
+            tee_stderr: None,
+            stdin_input: None,
+            exit_code_map: HashMap::new(),
+            output_capacity: (0, 0),
+            keep_last_bytes: None,
+            fallback_programs: Vec::new(),
+            resolve_dirs: Vec::new(),
+            stdio: StdioConfig::default(),
+            env_cleared: false,
+            env_fn: None,
+            #[cfg(all(
+                target_os = "linux",
+                any(target_arch = "x86_64", target_arch = "aarch64")
+            ))]
+            seccomp: None,
+        }
+    }
+
+    /// Creates a command from a [`std::process::Command`], e.g. one configured by another library.
+    ///
+    /// The program, arguments, environment changes and working directory are kept.
+    /// The standard streams and the platform-specific configuration of the std command are not,
+    /// and neither is whether its environment was cleared, since std doesn't expose them.
+    ///
+    /// A command converts back with `std::process::Command::from`, so configuration this crate doesn't expose
+    /// can be applied to the std command before spawning it directly.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::process::Command;
+    /// let mut std_command = std::process::Command::new("git");
+    /// std_command.args(["status"]).current_dir("/path/to/repo");
+    /// let command = Command::from_std(std_command);
+    /// ```
+    pub fn from_std(command: StdCommand) -> Self {
+        let mut cmd = Self::new(command.get_program()).args(command.get_args());
+        cmd.envs = command
+            .get_envs()
+            .map(|(key, value)| (key.to_os_string(), value.map(OsStr::to_os_string)))
+            .collect();
+        cmd.current_dir = command.get_current_dir().map(Path::to_path_buf);
+        cmd
+    }
+
+    /// Creates a command running `script` with the system shell, `sh -c <script>` on Unix and `cmd /C <script>` on Windows,
+    /// so a command line with pipes, redirects or variable expansion can be run as a whole.
+    ///
+    /// The environment, working directory and the rest of the configuration apply to the shell process.
+    ///
+    /// **The script is interpreted by the shell as is, nothing in it is escaped.**
+    /// Never build it from untrusted input such as file names or user-provided values,
+    /// since shell metacharacters in them (e.g. `;`, `|`, `$(...)` or `&`) run arbitrary commands.
+    /// Pass such values through [`Self::env`] and reference the variable in the script instead,
+    /// or spawn the program directly with its arguments.
+    ///
+    /// Arguments added with [`Self::args`] are not escaped into the script either:
+    /// `sh` receives them as `$0`, `$1` and so on, while on Windows they are appended to the command line verbatim.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::process::Command;
+    /// let name = "untrusted input";
+    /// let command = Command::shell("grep -c \"$NAME\" names.txt | tee count.txt").env("NAME", name);
+    /// ```
+    pub fn shell<S: Into<String>>(script: S) -> Self {
+        #[cfg(windows)]
+        {
+            let mut command = Self::new("cmd").args(["/C".to_string(), script.into()]);
+            command.raw_args = true;
+            command
+        }
+        #[cfg(not(windows))]
+        Self::new("sh").args(["-c".to_string(), script.into()])
+    }
+
+    /// Creates a command from the command line `cmdline`, e.g. one configured by the user in the app settings.
+    ///
+    /// The first word is the program and the rest are its arguments. The line is split into words
+    /// following the quoting rules of a POSIX shell, on every platform, but no shell is involved:
+    /// single quotes keep their content literally, double quotes allow escaping `$`, `` ` ``, `"` and `\`,
+    /// and a backslash outside of quotes escapes the next character. Variables, globs, pipes and redirects
+    /// are not interpreted, see [`Self::shell`] for that.
+    ///
+    /// Fails if the line has no words or an unterminated quote or escape.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::process::Command;
+    /// let command = Command::parse(r#"ffmpeg -i "my video.mp4" -vf 'scale=640:-1' out\ put.webm"#)?;
+    /// # Ok::<(), tauri_plugin_shell::Error>(())
+    /// ```
+    pub fn parse(cmdline: &str) -> crate::Result<Self> {
+        let mut words = cmdline::split(cmdline)?.into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| crate::Error::InvalidCommandLine("no program".into()))?;
+        Ok(Self::new(program).args(words))
+    }
+
+    /// Creates a command for the sidecar `program` next to the current executable, see [`crate::Shell::sidecar`].
+    pub(crate) fn new_sidecar<S: AsRef<Path>>(program: S) -> crate::Result<Self> {
+        Ok(Self::new(relative_command_path(program.as_ref())?))
+    }
+
+    /// Creates a command for `program` with the exact argument vector `argv`, see [`crate::Shell::command_with_argv`].
+    ///
+    /// The first element of `argv` is passed verbatim as the process `argv[0]` instead of the program path,
+    /// and the remaining elements are its arguments. This is useful for shims and multi-call binaries
+    /// that inspect the name they were invoked with.
+    ///
+    /// On Windows the process name can't be decoupled from the program,
+    /// so the first element of `argv` is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::process::Command;
+    /// let command = Command::with_argv("/bin/busybox".into(), vec!["ls".into(), "-l".into()]);
+    /// ```
+    pub fn with_argv(program: String, argv: Vec<String>) -> Self {
+        let mut command = Self::new(program);
+        let mut argv = argv.into_iter();
+        #[cfg(unix)]
+        {
+            command.arg0 = argv.next().map(Into::into);
+        }
+        #[cfg(not(unix))]
+        argv.next();
+        command.args(argv)
+    }
+
+    /// Returns the program that will be run, as passed to [`Self::new`].
+    pub fn get_program(&self) -> &OsStr {
+        &self.program
+    }
+
+    /// Returns the arguments that will be passed to the program, in order.
+    pub fn get_args(&self) -> impl Iterator<Item = &OsStr> {
+        self.args.iter().map(OsString::as_os_str)
+    }
+
+    /// Returns the explicit environment changes of the command in call order,
+    /// with a `None` value for the variables removed with [`Self::env_remove`].
+    ///
+    /// Unlike [`std::process::Command::get_envs`], a variable set multiple times is returned for each call.
+    pub fn get_envs(&self) -> impl Iterator<Item = (&OsStr, Option<&OsStr>)> {
+        self.envs
+            .iter()
+            .map(|(key, value)| (key.as_os_str(), value.as_deref()))
+    }
+
+    /// Returns the working directory of the child process, `None` if it inherits the current directory.
+    pub fn get_current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    fn reader_options(&self) -> ReaderOptions {
+        ReaderOptions {
+            raw_out: self.raw_out,
+            raw_chunk_size: self.raw_chunk_size,
+            max_line_len: self.max_line_len,
+            encoding: self.encoding,
+            line_prefix: self.line_prefix.clone(),
+            delimiter: self.delimiter,
+            normalize_newlines: self.normalize_newlines && !self.raw_out,
+            measure_first_output: self.measure_first_output,
+            first_output: None,
+            drain_on_kill: self.drain_on_kill,
+            killed: None,
+            max_events_per_sec: self.max_events_per_sec,
+            tee: None,
+            blocking: false,
+        }
+    }
+
+    /// Adds a program to run instead if the program of the command can't be found, e.g. `python` for `python3`.
+    ///
+    /// The fallbacks are tried in the order they're added, with the same arguments, environment and options.
+    /// If none of them can be found, spawning fails with [`crate::Error::ProgramNotFound`] listing every attempted program.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (_rx, _child) = app.shell().command("python3")
+    ///       .or_program("python")
+    ///       .or_program("py")
+    ///       .args(["script.py"])
+    ///       .spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn or_program<S: AsRef<OsStr>>(mut self, program: S) -> Self {
+        self.fallback_programs.push(program.as_ref().to_os_string());
+        self
+    }
+
+    /// Searches `dir` for the program before the `PATH` of the child process, e.g. to run the binaries of
+    /// a toolchain bundled with the app without modifying the global `PATH`.
+    ///
+    /// The directories are searched in the order they're added and the program is spawned from its absolute path.
+    /// Programs given as a path instead of a name are not searched for. If the program can't be found
+    /// in any of the directories nor the `PATH`, spawning fails with [`crate::Error::ProgramNotFound`]
+    /// listing every candidate path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri::Manager;
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let toolchain = app.path().resource_dir()?.join("toolchain").join("bin");
+    ///     let (_rx, _child) = app.shell().command("node").resolve_in(toolchain).args(["server.js"]).spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn resolve_in(mut self, dir: PathBuf) -> Self {
+        self.resolve_dirs.push(dir);
+        self
+    }
+
+    /// Appends an argument to the command.
+    ///
+    /// Can be chained with [`Self::args`], the arguments are passed in the order they're appended.
+    #[must_use]
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Appends arguments to the command.
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// Clears the entire environment map for the child process.
+    #[must_use]
+    pub fn env_clear(mut self) -> Self {
+        self.envs.clear();
+        self.env_cleared = true;
+        self
+    }
+
+    /// Inserts or updates an explicit environment variable mapping.
+    ///
+    /// Variables set by previous calls to this method or [`Self::envs`] are kept.
+    #[must_use]
+    pub fn env<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs.push((
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        ));
+        self
+    }
+
+    /// Adds or updates multiple environment variable mappings.
+    ///
+    /// This extends the variables set by previous calls to this method or [`Self::env`] instead of replacing them,
+    /// later mappings take precedence over earlier ones for the same variable.
+    #[must_use]
+    pub fn envs<I, K, V>(mut self, envs: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.envs.extend(envs.into_iter().map(|(key, value)| {
+            (
+                key.as_ref().to_os_string(),
+                Some(value.as_ref().to_os_string()),
+            )
+        }));
+        self
+    }
+
+    /// Removes an environment variable, whether it's inherited from the parent process or set by [`Self::env`] or [`Self::envs`].
+    ///
+    /// Changes are applied in call order, so this removes the variables set by earlier calls,
+    /// and a later call to [`Self::env`] sets the variable again.
+    #[must_use]
+    pub fn env_remove<K: AsRef<OsStr>>(mut self, key: K) -> Self {
+        self.envs.push((key.as_ref().to_os_string(), None));
+        self
+    }
+
+    /// Sets a hook that modifies the environment of the child process right before it is spawned.
+    ///
+    /// The hook is called once at spawn time with the path of the program that will run,
+    /// resolved from the `PATH` of the child environment, and its effective environment:
+    /// the parent environment (or an empty one after [`Self::env_clear`]) with the variables set by
+    /// [`Self::env`] and [`Self::envs`] applied, regardless of the order the methods were called in.
+    /// Changes made by the hook take precedence. Variables that are not valid UTF-8 are not exposed to the hook
+    /// and are passed to the child untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::sync::Arc;
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let command = app.shell().command("node").env_fn(Arc::new(|program, env| {
+    ///       if program.ends_with("node") {
+    ///         env.insert("NODE_OPTIONS".into(), "--max-old-space-size=4096".into());
+    ///       }
+    ///     }));
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn env_fn(mut self, f: EnvFn) -> Self {
+        self.env_fn.replace(EnvHook(f));
+        self
+    }
+
+    /// Sets the working directory for the child process.
+    #[must_use]
+    pub fn current_dir<P: AsRef<Path>>(mut self, current_dir: P) -> Self {
+        self.current_dir.replace(current_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Collects the resolved program, arguments, environment changes and working directory the command would be spawned with.
+    ///
+    /// Unlike formatting the command, this is machine-readable and reflects everything applied at spawn time,
+    /// such as [`Self::env_fn`] and [`Self::stdbuf_line`], making it useful to attach to error reports.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let command = app.shell().sidecar("converter")?.args(["--fast"]);
+    ///     let diagnostics = command.diagnostics();
+    ///     if let Err(e) = command.spawn() {
+    ///       log::error!("failed to spawn: {e}, {}", serde_json::to_string(&diagnostics)?);
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn diagnostics(&self) -> CommandDiagnostics {
+        let command: StdCommand = self.clone().into();
+        let env = effective_env(&command, env_cleared(self));
+        // not the `stdbuf` wrapper, see `Self::stdbuf_line`
+        let program = resolve_in_dirs(self).map_or_else(|| self.program.clone(), Into::into);
+
+        let mut env_set = BTreeMap::new();
+        let mut env_removed = Vec::new();
+        let parent_env: HashMap<OsString, OsString> = std::env::vars_os().collect();
+        for (key, value) in &env {
+            if parent_env.get(key) != Some(value) {
+                env_set.insert(
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                );
+            }
+        }
+        for key in parent_env.keys() {
+            if !env.contains_key(key) {
+                env_removed.push(key.to_string_lossy().into_owned());
+            }
+        }
+        env_removed.sort();
+
+        CommandDiagnostics {
+            resolved_program: resolve_program(&program, env_path(&env)),
+            argv: std::iter::once(command.get_program())
+                .chain(command.get_args())
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            env_set,
+            env_removed,
+            current_dir: command
+                .get_current_dir()
+                .map(Path::to_path_buf)
+                .or_else(|| std::env::current_dir().ok()),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+
+    /// Forces the child process to line buffer its stdout and stderr by running it through `stdbuf -oL -eL`.
+    ///
+    /// Many programs only line buffer their output when it's connected to a terminal and fully buffer it into a pipe,
+    /// so their output events arrive in large chunks or only once they exit. This fixes it for the programs
+    /// using the C standard I/O library without allocating a pseudo-terminal, but has no effect on programs
+    /// managing their own buffers.
+    ///
+    /// If `stdbuf` (part of GNU coreutils) can't be found in `PATH`, or a custom `argv[0]` is set,
+    /// the program is run directly and a warning is logged.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn stdbuf_line(mut self) -> Self {
+        self.stdbuf_line = true;
+        self
+    }
+
+    /// Configures whether a console program gets a console window on Windows, e.g. for an interactive installer.
+    ///
+    /// Disabled by default, so no window flashes when the app runs a console program in the background.
+    /// Has no effect on other platforms.
+    #[must_use]
+    pub fn show_window(self, show: bool) -> Self {
+        #[cfg(windows)]
+        {
+            Self {
+                show_window: show,
+                ..self
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = show;
+            self
+        }
+    }
+
+    /// Sets the [process creation flags](https://learn.microsoft.com/en-us/windows/win32/procthread/process-creation-flags)
+    /// of the child process, e.g. `CREATE_NEW_PROCESS_GROUP` to send it Ctrl+Break events
+    /// or `BELOW_NORMAL_PRIORITY_CLASS` to lower its priority.
+    ///
+    /// The flags are combined with `CREATE_NO_WINDOW`, unless [`Self::show_window`] is enabled.
+    /// Calling this again replaces the previous flags.
+    #[cfg(windows)]
+    #[must_use]
+    pub fn creation_flags(mut self, flags: u32) -> Self {
+        self.creation_flags = flags;
+        self
+    }
+
+    /// Configures whether the child process and every process it spawns are contained in a job object,
+    /// which terminates all of them once the [`CommandChild`] is dropped or the app exits.
+    ///
+    /// This is the Windows counterpart of [`Self::process_group`] on Unix: killing a process doesn't kill
+    /// the processes it spawned, but dropping the job does. [`CommandChild::kill`] consumes the child,
+    /// so it kills the whole job too.
+    #[cfg(windows)]
+    #[must_use]
+    pub fn contain(mut self, contain: bool) -> Self {
+        self.contain = contain;
+        self
+    }
+
+    /// Sets the user id the child process runs as, e.g. to drop root privileges before running a sidecar.
+    ///
+    /// The id is changed in the child right before the program is executed, and spawning fails if it can't be changed.
+    /// When the app runs as root, the supplementary groups of the child are cleared too.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.uid.replace(uid);
+        self
+    }
+
+    /// Sets the group id the child process runs as, see [`Self::uid`].
+    #[cfg(unix)]
+    #[must_use]
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.gid.replace(gid);
+        self
+    }
+
+    /// Configures whether the child process is put in a new process group, along with the processes it spawns.
+    ///
+    /// When enabled, [`CommandChild::kill`] kills the whole group, so a program spawning its own children,
+    /// e.g. a shell running a pipeline, doesn't leave orphaned processes behind.
+    /// The group is only killed while the child itself is running, since its id can be reused once it exited.
+    /// The group also doesn't receive the signals sent to the foreground process group of the terminal, e.g. on Ctrl+C.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn process_group(mut self, process_group: bool) -> Self {
+        self.process_group = process_group;
+        self
+    }
+
+    /// Sets the scheduling priority of the child process, e.g. [`ProcessPriority::Low`] so a build tool
+    /// or media encoder doesn't starve the app of CPU time.
+    ///
+    /// On Unix the nice value documented on each [`ProcessPriority`] variant is set in the child right before
+    /// the program is executed. Spawning fails if it can't be set, e.g. when raising the priority without privileges.
+    /// On Windows the matching priority class is added to the creation flags.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::ProcessPriority, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (_rx, _child) = app.shell().sidecar("ffmpeg")?.priority(ProcessPriority::Low).spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn priority(mut self, priority: ProcessPriority) -> Self {
+        self.priority.replace(priority);
+        self
+    }
+
+    /// Limits a resource of the child process with `setrlimit`, e.g. [`Resource::AddressSpace`]
+    /// so an untrusted sidecar can't exhaust the memory of the machine.
+    ///
+    /// The limits are applied in the child right before the program is executed, in the order they're added,
+    /// so a later limit of the same resource overrides an earlier one.
+    /// Spawning fails if a limit can't be set, e.g. when raising a hard limit without privileges.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::Resource, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (_rx, _child) = app.shell().sidecar("plugin-host")?
+    ///       .rlimit(Resource::AddressSpace, 512 * 1024 * 1024, 512 * 1024 * 1024)
+    ///       .rlimit(Resource::Cpu, 60, 60)
+    ///       .spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[cfg(unix)]
+    #[must_use]
+    pub fn rlimit(mut self, resource: Resource, soft: u64, hard: u64) -> Self {
+        self.rlimits.push((resource, soft, hard));
+        self
+    }
+
+    /// Sets the file mode creation mask of the child process, e.g. `0o077` to make the files it creates
+    /// only accessible by the current user.
+    ///
+    /// The mask is applied in the child right before the program is executed,
+    /// so the umask of the current process is not affected.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn umask(mut self, mode: u32) -> Self {
+        self.umask.replace(mode);
+        self
+    }
+
+    /// Restricts the syscalls the child process can make with a seccomp-bpf filter, e.g. [`SeccompFilter::no_network`].
+    ///
+    /// The filter is installed right before the program is executed, after every other setup step,
+    /// and is inherited by all the descendants of the process. It also sets the `no_new_privs` flag on the process,
+    /// so set-user-ID programs can't gain privileges. Calling this again replaces the previous filter.
+    ///
+    /// Requires Linux 3.5 or newer, and 4.14 or newer to kill processes using a foreign syscall ABI.
+    /// Only available on `x86_64` and `aarch64`.
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[must_use]
+    pub fn seccomp(mut self, filter: SeccompFilter) -> Self {
+        self.seccomp.replace(filter);
+        self
+    }
+
+    /// Configures the reader to output bytes from the child process exactly as received
+    ///
+    /// The output isn't split on line endings, so binary data is passed through unchanged.
+    /// Each event contains the bytes available when the pipe was read, up to [`Self::raw_chunk_size`].
+    pub fn set_raw_out(mut self, raw_out: bool) -> Self {
+        self.raw_out = raw_out;
+        self
+    }
+
+    /// Sets the maximum size of the output events when [`Self::set_raw_out`] is enabled, defaults to 4096 bytes.
+    ///
+    /// A size of zero is treated as one.
+    #[must_use]
+    pub fn raw_chunk_size(mut self, size: usize) -> Self {
+        self.raw_chunk_size = size.max(1);
+        self
+    }
+
+    /// Limits the size of the line events, e.g. so a process writing a huge amount of output
+    /// without line endings can't exhaust the memory of the app.
+    ///
+    /// A line longer than `len` bytes is emitted in chunks of `len` bytes, the last one with the line ending.
+    /// The chunks can split a multi-byte character unless an [`Self::encoding`] is set.
+    /// By default lines are unlimited and buffered until their line ending is read.
+    /// A length of zero is treated as one.
+    #[must_use]
+    pub fn max_line_len(mut self, len: usize) -> Self {
+        self.max_line_len.replace(len.max(1));
+        self
+    }
+
+    /// Decodes the stdout and stderr output of the child process from `encoding` to UTF-8,
+    /// e.g. [`encoding_rs::GBK`] or [`encoding_rs::SHIFT_JIS`] for programs using a legacy Windows code page.
+    ///
+    /// By default the output is emitted exactly as the process writes it, which is usually UTF-8.
+    /// Invalid sequences are replaced with `U+FFFD`, and a byte order mark at the start of the output is removed.
+    /// Lines are split before decoding, so encodings that aren't ASCII compatible such as UTF-16 aren't supported in line mode.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::Encoding, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let encoding = Encoding::for_label(b"gbk").unwrap();
+    ///     let lines = tauri::async_runtime::block_on(
+    ///       app.shell().command("ipconfig").encoding(encoding).stdout_lines(),
+    ///     )?;
+    ///     println!("{lines:?}");
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.encoding.replace(encoding);
+        self
+    }
+
+    /// Splits the output events on `delimiter` instead of line endings, e.g. `b'\0'` for the output of `find -print0`.
+    ///
+    /// Like line endings, the delimiter is kept at the end of each chunk.
+    /// It has no effect when [`Self::set_raw_out`] is enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (mut rx, _child) = app.shell().command("find")
+    ///       .args([".", "-name", "*.rs", "-print0"])
+    ///       .delimiter(b'\0')
+    ///       .spawn()?;
+    ///     tauri::async_runtime::spawn(async move {
+    ///       while let Some(CommandEvent::Stdout(path)) = rx.recv().await {
+    ///         println!("found {}", String::from_utf8_lossy(&path[..path.len() - 1]));
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter.replace(delimiter);
+        self
+    }
+
+    /// Converts the `\r\n` and lone `\r` line endings of the output to `\n`,
+    /// so the output is the same regardless of the platform conventions of the program.
+    ///
+    /// This applies to the output events and to the output collected by [`Self::output`].
+    /// A `\r\n` split across two reads is still converted to a single `\n`.
+    /// It has no effect when [`Self::set_raw_out`] is enabled, which emits the output as is.
+    #[must_use]
+    pub fn normalize_newlines(mut self) -> Self {
+        self.normalize_newlines = true;
+        self
+    }
+
+    /// Prepends `prefix` to every chunk of stdout and stderr output emitted by the reader.
+    ///
+    /// The prefix is applied last, after any other output transformation,
+    /// which makes it easy to tag the output of several children sharing a single sink
+    /// e.g. `[sidecar-a] listening on port 8080`.
+    #[must_use]
+    pub fn line_prefix(mut self, prefix: Vec<u8>) -> Self {
+        self.line_prefix.replace(prefix);
+        self
+    }
+
+    /// Writes `input` to the stdin of the process, closing it afterwards.
+    ///
+    /// The input is encoded with the [`Self::encoding`] of the command, or UTF-8 by default, like [`CommandChild::write_str`].
+    /// The input is written from a separate thread as the process consumes it, so large inputs don't block the spawn call.
+    /// Stdin is owned by the feeder, so [`CommandChild::write`] fails. Failures to write the input are reported as
+    /// [`CommandEvent::Error`] events, unless the process exits or closes its stdin before reading all of it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let output = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("sort").stdin_str("b\na\n".into()).output().await
+    ///     })?;
+    ///     println!("{}", String::from_utf8_lossy(&output.stdout));
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn stdin_str(mut self, input: String) -> Self {
+        self.stdin_input.replace(StdinInput::Text(input.into()));
+        self
+    }
+
+    /// Writes `input` to the stdin of the process, closing it afterwards, see [`Self::stdin_str`].
+    #[must_use]
+    pub fn stdin_bytes(mut self, input: Vec<u8>) -> Self {
+        self.stdin_input.replace(StdinInput::Data(input.into()));
+        self
+    }
+
+    /// Copies the content of the file at `path` to the stdin of the process, closing it afterwards, see [`Self::stdin_str`].
+    ///
+    /// The file is opened when spawning, which fails if it can't be opened, and is streamed as the process consumes it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let output = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("gzip").stdin_file("report.csv".into()).set_raw_out(true).output().await
+    ///     })?;
+    ///     println!("compressed to {} bytes", output.stdout.len());
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn stdin_file(mut self, path: PathBuf) -> Self {
+        self.stdin_input.replace(StdinInput::File(path));
+        self
+    }
+
+    /// Writes the stdout of the child process to the file at `path`, created or truncated when spawning.
+    ///
+    /// The output goes straight to the file without flowing through the app, so no reader task is spawned for it
+    /// and [`CommandEvent::Stdout`] is never emitted. Use [`Self::tee_stdout`] to both write the output to a file
+    /// and receive it as events.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (_rx, _child) = app.shell().sidecar("server")?
+    ///       .stdout_file("server.log".into())
+    ///       .stderr_file("server.err.log".into())
+    ///       .spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn stdout_file(mut self, path: PathBuf) -> Self {
+        self.stdio.stdout = StdioMode::File(path);
+        self
+    }
+
+    /// Writes the stderr of the child process to the file at `path`, see [`Self::stdout_file`].
+    #[must_use]
+    pub fn stderr_file(mut self, path: PathBuf) -> Self {
+        self.stdio.stderr = StdioMode::File(path);
+        self
+    }
+
+    /// Connects the stdin, stdout and stderr of the child process to the ones of the parent process,
+    /// so interactive tools that need the controlling terminal (e.g. `vim`, `sudo` or `ssh` prompting for a password) work as expected.
+    ///
+    /// Unlike allocating a pseudo-terminal, the child uses the parent's terminal directly.
+    /// In this mode the output is written straight to the terminal and doesn't flow through
+    /// [`CommandEvent::Stdout`] and [`CommandEvent::Stderr`] events, and [`CommandChild::write`] fails.
+    /// The [`CommandEvent::Terminated`] event is still emitted.
+    #[cfg(unix)]
+    #[must_use]
+    pub fn inherit_tty(self) -> Self {
+        self.inherit_stdio()
+    }
+
+    /// Connects the stdin, stdout and stderr of the child process to the ones of the parent process,
+    /// e.g. so the colored output of a CLI tool shows up in the terminal the app was started from.
+    ///
+    /// No pipes or reader tasks are created: [`CommandEvent::Stdout`] and [`CommandEvent::Stderr`] are never emitted
+    /// and [`CommandChild::write`] fails. The [`CommandEvent::Terminated`] event is still emitted.
+    #[must_use]
+    pub fn inherit_stdio(mut self) -> Self {
+        self.stdio = StdioConfig {
+            stdin: StdioMode::Inherit,
+            stdout: StdioMode::Inherit,
+            stderr: StdioMode::Inherit,
+        };
+        self
+    }
+
+    /// Discards the stdout and stderr of the child process by connecting them to the null device,
+    /// e.g. when only its exit status matters.
+    ///
+    /// No pipes or reader tasks are created for them, so [`CommandEvent::Stdout`] and [`CommandEvent::Stderr`]
+    /// are never emitted. [`Self::status`] already discards the output this way.
+    #[must_use]
+    pub fn null_stdio(mut self) -> Self {
+        self.stdio.stdout = StdioMode::Null;
+        self.stdio.stderr = StdioMode::Null;
+        self
+    }
+
+    /// Writes the stderr of the child process to its stdout, like `2>&1` in a shell.
+    ///
+    /// Both streams share a single pipe, so the output is emitted in the order the process wrote it,
+    /// as a terminal would show it. In this mode [`CommandEvent::Stderr`] events never fire and
+    /// the stderr output is emitted as [`CommandEvent::Stdout`] events instead.
+    /// The stderr output follows stdout wherever it goes, e.g. to the file set with [`Self::stdout_file`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (mut rx, _child) = app.shell().command("cargo").args(["build"]).merge_stderr_into_stdout().spawn()?;
+    ///     tauri::async_runtime::spawn(async move {
+    ///       while let Some(CommandEvent::Stdout(line)) = rx.recv().await {
+    ///         print!("{}", String::from_utf8_lossy(&line));
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn merge_stderr_into_stdout(mut self) -> Self {
+        self.stdio.stderr = StdioMode::Stdout;
+        self
+    }
+
+    /// Discards the output that would only be read to be ignored,
+    /// leaving the streams redirected to a file or teed untouched.
+    fn discard_output(mut self) -> Self {
+        if self.stdio.stdout == StdioMode::Piped && self.tee_stdout.is_none() {
+            self.stdio.stdout = StdioMode::Null;
+        }
+        if self.stdio.stderr == StdioMode::Piped && self.tee_stderr.is_none() {
+            self.stdio.stderr = StdioMode::Null;
+        }
+        self
+    }
+
+    /// Configures whether the output still buffered in the pipes is emitted after [`CommandChild::kill`] is called.
+    ///
+    /// When enabled, the readers drain the pipes and emit the remaining output before the
+    /// [`CommandEvent::Terminated`] event, giving a complete log tail.
+    /// When disabled, the output read after the kill is discarded, for a faster and cleaner stop.
+    ///
+    /// Enabled by default.
+    #[must_use]
+    pub fn drain_on_kill(mut self, drain: bool) -> Self {
+        self.drain_on_kill = drain;
+        self
+    }
+
+    /// Sets how many events can be queued in the event channel before the readers wait for the receiver, defaults to 1.
+    ///
+    /// A larger buffer lets a program producing output quickly keep running while the receiver catches up,
+    /// at the cost of memory. A capacity of zero is treated as one.
+    #[must_use]
+    pub fn event_buffer(mut self, capacity: usize) -> Self {
+        self.event_buffer = capacity.max(1);
+        self
+    }
+
+    /// Configures whether the process is killed when its [`CommandChild`] is dropped.
+    ///
+    /// Useful to tie the lifetime of the process to the handle, e.g. when it's stored in a window state,
+    /// so the process doesn't outlive the window that started it.
+    ///
+    /// Disabled by default, so the process keeps running after the handle is dropped.
+    #[must_use]
+    pub fn kill_on_drop(mut self, kill_on_drop: bool) -> Self {
+        self.kill_on_drop = kill_on_drop;
+        self
+    }
+
+    /// Sets the documented meaning of the exit codes of the program, e.g. `23 => "partial transfer due to error"` for `rsync`.
+    ///
+    /// When the process exits with one of these codes, [`Self::output_checked`] and [`Self::output_to`] fail
+    /// with [`crate::Error::KnownExit`] carrying the message instead of [`crate::Error::NonZeroExit`].
+    #[must_use]
+    pub fn exit_code_map(mut self, map: HashMap<i32, String>) -> Self {
+        self.exit_code_map = map;
+        self
+    }
+
+    /// Preallocates the buffers [`Self::output`] collects stdout and stderr into, in bytes.
+    ///
+    /// This avoids reallocations when capturing a large output of a known size. Defaults to `0` for both.
+    #[must_use]
+    pub fn output_capacity(mut self, stdout: usize, stderr: usize) -> Self {
+        self.output_capacity = (stdout, stderr);
+        self
+    }
+
+    /// Makes [`Self::output`] keep only the last `n` bytes of stdout and of stderr.
+    ///
+    /// The output is bounded by bytes rather than lines, so memory stays bounded even for binary output or very long lines.
+    /// The total number of bytes the process wrote is still reported in [`Output::stdout_total`] and [`Output::stderr_total`].
+    #[must_use]
+    pub fn keep_last_bytes(mut self, n: usize) -> Self {
+        self.keep_last_bytes.replace(n);
+        self
+    }
+
+    /// Limits the number of output events emitted per second for each of stdout and stderr.
+    ///
+    /// Output read within the same window of `1 / rate` seconds after an event is emitted
+    /// is coalesced into a single [`CommandEvent::StdoutBatch`] or [`CommandEvent::StderrBatch`] event,
+    /// emitted once the window ends. This protects consumers, e.g. the webview, from an overly chatty process.
+    ///
+    /// A rate of `0` disables the limit, which is the default.
+    #[must_use]
+    pub fn max_events_per_sec(mut self, rate: u32) -> Self {
+        self.max_events_per_sec = (rate > 0).then_some(rate);
+        self
+    }
+
+    /// Delivers the events of the `priority` stream first when both stdout and stderr have pending events,
+    /// e.g. so error lines are not delayed behind a flood of stdout output.
+    ///
+    /// By default both streams compete for the event channel and the order under contention is unspecified.
+    /// With a priority, the output of both streams is merged by a dispatcher thread instead.
+    /// The order of the events of a single stream is always preserved.
+    #[must_use]
+    pub fn stream_priority(mut self, priority: Priority) -> Self {
+        self.stream_priority.replace(priority);
+        self
+    }
+
+    /// Writes the stdout of the process to `file`, in addition to emitting it as events.
+    ///
+    /// The output is written exactly as read, before [`Self::line_prefix`] is applied.
+    /// Write failures are reported as [`CommandEvent::Error`] events without interrupting the output stream.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let log = std::fs::File::create("server.log")?;
+    ///     let (_rx, _child) = app.shell().sidecar("server")?.tee_stdout(log).spawn()?;
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn tee_stdout(mut self, file: File) -> Self {
+        self.tee_stdout.replace(Arc::new(file));
+        self
+    }
+
+    /// Writes the stderr of the process to `file`, in addition to emitting it as events.
+    ///
+    /// See [`Self::tee_stdout`].
+    #[must_use]
+    pub fn tee_stderr(mut self, file: File) -> Self {
+        self.tee_stderr.replace(Arc::new(file));
+        self
+    }
+
+    /// Emits a [`CommandEvent::FirstOutput`] event with the time elapsed between the spawn call
+    /// and the first byte of output read from the process.
+    ///
+    /// This is useful to profile the cold start of sidecars. The event is emitted once per process,
+    /// when the first chunk of either stdout or stderr is read, usually right before the corresponding output event.
+    #[must_use]
+    pub fn measure_first_output(mut self) -> Self {
+        self.measure_first_output = true;
+        self
+    }
+
+    /// Configures the delay between restarts of a command spawned with [`Self::spawn_supervised`].
+    ///
+    /// The first restart happens after `initial`, and each consecutive failure multiplies the delay by `factor`,
+    /// up to `max`. The failure count is reset when the process stays up for at least `max`,
+    /// so a process that crashes once after running for a long time is restarted quickly.
+    ///
+    /// Defaults to an initial delay of 500ms, a maximum of 30 seconds and a factor of 2.
+    #[must_use]
+    pub fn restart_backoff(mut self, initial: Duration, max: Duration, factor: f64) -> Self {
+        self.restart_backoff.replace(RestartBackoff {
+            initial,
+            max,
+            factor,
+        });
+        self
+    }
+
+    /// Configures the command to capture its stdout and stderr both separately and merged.
+    ///
+    /// The returned [`DualCapture::output`] collects a [`DualOutput`] which includes
+    /// a `merged` buffer in addition to the separated `stdout` and `stderr` buffers,
+    /// so the command doesn't need to be executed twice.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let output = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("cargo").args(["build"]).dual_capture().output().await.unwrap()
+    ///     });
+    ///     println!("{}", String::from_utf8_lossy(&output.merged));
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn dual_capture(self) -> DualCapture {
+        DualCapture { command: self }
+    }
+
+    /// Configures the command to run as a detached process that keeps running after the app exits, e.g. a background helper.
+    ///
+    /// The returned [`DetachedCommand::spawn`] only returns the pid of the process, since its output isn't captured.
+    /// On Unix the process runs in a new session, so it doesn't receive the signals sent to the terminal of the app.
+    /// The session has its own process group, so [`Self::process_group`] is implied.
+    /// On Windows it's created with `DETACHED_PROCESS`, so it has no console.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let pid = app.shell().sidecar("updater")?.detached().spawn()?;
+    ///     println!("updater running with pid {pid}");
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[must_use]
+    pub fn detached(self) -> DetachedCommand {
+        DetachedCommand { command: self }
+    }
+
+    /// Spawns the command.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let handle = app.handle().clone();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       let (mut rx, mut child) = handle.shell().command("cargo")
+    ///         .args(["tauri", "dev"])
+    ///         .spawn()
+    ///         .expect("Failed to spawn cargo");
+    ///
+    ///       let mut i = 0;
+    ///       while let Some(event) = rx.recv().await {
+    ///         if let CommandEvent::Stdout(line) = event {
+    ///           println!("got: {}", String::from_utf8(line.into()).unwrap());
+    ///           i += 1;
+    ///           if i == 4 {
+    ///             child.write("message from Rust\n".as_bytes()).unwrap();
+    ///             i = 0;
+    ///           }
+    ///         }
+    ///       }
+    ///     });
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn spawn(self) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+        let source = self.clone();
+        let mut command: StdCommand = self.into();
+        spawn_child(&source, &mut command)
+    }
+
+    /// Spawns the command like [`Self::spawn`], killing the process once `token` is cancelled,
+    /// e.g. to stop a long-running job when the window or task that started it goes away,
+    /// without having to keep the [`CommandChild`] around.
+    ///
+    /// The output still buffered in the pipes is emitted like with [`CommandChild::kill`].
+    /// The [`CommandEvent::Terminated`] event of a cancelled process has no exit code
+    /// and [`CANCELLED_SIGNAL`] as its signal, so it can be told apart from one that finished or was killed otherwise.
+    /// A process exiting on its own right as the token is cancelled might be reported either way.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{
+    ///   process::{CancellationToken, CommandEvent, CANCELLED_SIGNAL},
+    ///   ShellExt,
+    /// };
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let token = CancellationToken::new();
+    ///     let (mut rx, _child) = app.shell().command("cargo").args(["build"]).spawn_with_cancel(token.child_token())?;
+    ///     tauri::async_runtime::spawn(async move {
+    ///       while let Some(event) = rx.recv().await {
+    ///         if let CommandEvent::Terminated(payload) = event {
+    ///           println!("cancelled: {}", payload.signal == Some(CANCELLED_SIGNAL));
+    ///         }
+    ///       }
+    ///     });
+    ///     // e.g. when the user closes the window
+    ///     token.cancel();
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_with_cancel(
+        self,
+        token: CancellationToken,
+    ) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+        let (rx, child) = self.spawn()?;
+        let inner = child.inner.clone();
+        let command = child.command.clone();
+        let killed = child.killed.clone();
+        let cancelled = child.cancelled.clone();
+        let exit = child.subscribe_exit();
+        spawn_task(async move {
+            let mut cancel = std::pin::pin!(token.cancelled_owned());
+            let mut exit = std::pin::pin!(exit);
+            // resolves as soon as the process exits, so the task doesn't outlive it
+            let cancel = std::future::poll_fn(|cx| match cancel.as_mut().poll(cx) {
+                std::task::Poll::Ready(()) => std::task::Poll::Ready(true),
+                std::task::Poll::Pending => exit.as_mut().poll(cx).map(|_| false),
+            });
+            if cancel.await && inner.try_wait().is_ok_and(|status| status.is_none()) {
+                cancelled.store(true, Ordering::Relaxed);
+                killed.store(true, Ordering::Relaxed);
+                let _ = kill_process(&inner, &command);
+            }
+        });
+        Ok((rx, child))
+    }
+
+    /// Spawns the command, delivering its events over a [`std::sync::mpsc`] channel.
+    ///
+    /// Unlike [`Self::spawn`], the events are sent without involving the async runtime at all,
+    /// so the receiver can be drained from any thread, e.g. in tests or in a host without a Tauri runtime.
+    /// The output is read by a thread per stream instead of tasks on the runtime.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (rx, _child) = app.shell().sidecar("indexer")?.spawn_sync_channel()?;
+    ///     std::thread::spawn(move || {
+    ///       for event in rx {
+    ///         if let CommandEvent::Stdout(line) = event {
+    ///           println!("got: {}", String::from_utf8_lossy(&line));
+    ///         }
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_sync_channel(self) -> crate::Result<(mpsc::Receiver<CommandEvent>, CommandChild)> {
+        let source = self.clone();
+        let mut command: StdCommand = self.into();
+        let (tx, rx) = mpsc::sync_channel(source.event_buffer);
+        let child =
+            spawn_child_with_sender(&source, &mut command, EventSender::Sync(Arc::new(tx)))?;
+        Ok((rx, child))
+    }
+
+    /// Spawns the command like [`Self::spawn`], stamping each event with the time it was produced,
+    /// e.g. to record a terminal session that can be replayed with accurate timing.
+    ///
+    /// The output events are stamped by the reader tasks right after the output is read and before
+    /// waiting for the channel to have capacity, so the timestamps are not skewed by a slow receiver.
+    /// With [`Self::stream_priority`] or [`Self::max_events_per_sec`], the output is stamped
+    /// when it leaves the dispatcher or the rate limiter instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Instant;
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let started = Instant::now();
+    ///     let (mut rx, _child) = app.shell().command("cargo").args(["build"]).spawn_timestamped()?;
+    ///     tauri::async_runtime::spawn(async move {
+    ///       while let Some((at, event)) = rx.recv().await {
+    ///         if let CommandEvent::Stdout(line) = event {
+    ///           println!("[{:?}] {}", at - started, String::from_utf8_lossy(&line));
+    ///         }
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_timestamped(
+        self,
+    ) -> crate::Result<(Receiver<(Instant, CommandEvent)>, CommandChild)> {
+        let source = self.clone();
+        let mut command: StdCommand = self.into();
+        let (tx, rx) = channel(source.event_buffer);
+        let child = spawn_child_with_sender(&source, &mut command, EventSender::Timestamped(tx))?;
+        Ok((rx, child))
+    }
+
+    /// Spawns the command attached to a new pseudo-terminal of `rows` by `cols`,
+    /// so programs that only behave interactively on a terminal (e.g. shells, REPLs or progress bars) can be driven
+    /// and rendered by the app.
+    ///
+    /// The process is the leader of a new session with the pseudo-terminal as its controlling terminal.
+    /// The session has its own process group, so [`Self::process_group`] is implied.
+    /// Its stdout and stderr are merged and delivered as [`CommandEvent::Stdout`] events, with the terminal's
+    /// `\r\n` line endings and escape sequences left untouched, and [`CommandChild::write`] writes to the terminal,
+    /// which echoes the input back by default. Use [`CommandChild::resize`] to change the window size.
+    ///
+    /// The stdio configuration of the command is ignored. Only available on Unix with the `pty` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (mut rx, mut child) = app.shell().command("bash").set_raw_out(true).spawn_pty(24, 80)?;
+    ///     child.write_line("ls --color")?;
+    ///     child.resize(40, 120)?;
+    ///     tauri::async_runtime::spawn(async move {
+    ///       while let Some(event) = rx.recv().await {
+    ///         if let CommandEvent::Stdout(chunk) = event {
+    ///           println!("{}", String::from_utf8_lossy(&chunk));
+    ///         }
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    #[cfg(all(unix, feature = "pty"))]
+    pub fn spawn_pty(
+        mut self,
+        rows: u16,
+        cols: u16,
+    ) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+        self.pty.replace((rows, cols));
+        self.spawn()
+    }
+
+    /// Spawns the command, broadcasting each of its events to every subscriber of the returned handle,
+    /// e.g. to log the output while rendering it in a terminal view.
+    ///
+    /// Unlike the other spawn methods, the process is never slowed down by a slow consumer:
+    /// the channel holds the last `capacity` events and a subscriber that falls further behind skips
+    /// the oldest ones, receiving [`broadcast::error::RecvError::Lagged`] with the number of skipped events.
+    /// Events sent while there are no subscribers are dropped. A capacity of zero is treated as one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (events, _child) = app.shell().command("cargo").args(["build"]).spawn_broadcast(1024)?;
+    ///     for pane in ["log", "terminal"] {
+    ///       let mut rx = events.subscribe();
+    ///       tauri::async_runtime::spawn(async move {
+    ///         while let Ok(event) = rx.recv().await {
+    ///           if let CommandEvent::Stdout(line) = event {
+    ///             println!("[{pane}] {}", String::from_utf8_lossy(&line));
+    ///           }
+    ///         }
+    ///       });
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_broadcast(self, capacity: usize) -> crate::Result<(EventBroadcast, CommandChild)> {
+        let source = self.clone();
+        let mut command: StdCommand = self.into();
+        let (tx, rx) = broadcast::channel(capacity.max(1));
+        let tx = Arc::new(tx);
+        let events = EventBroadcast {
+            sender: Arc::downgrade(&tx),
+            first: Arc::new(Mutex::new(Some(rx))),
+        };
+        let child = spawn_child_with_sender(&source, &mut command, EventSender::Broadcast(tx))?;
+        Ok((events, child))
+    }
+
+    /// Spawns the command and waits for a line of its stdout to match the `pattern` regex,
+    /// returning the capture groups of the match alongside the still running child.
+    ///
+    /// This is the shape of scraping a value printed at startup, e.g. the local URL of a dev server.
+    /// Groups that didn't participate in the match are returned as empty strings.
+    /// The output following the match is discarded so the process never blocks writing to its pipes.
+    ///
+    /// Fails with [`crate::Error::NoMatch`] if the process exits before printing a matching line.
+    /// Dropping the returned future before a match kills the process,
+    /// see [`Self::spawn_until_match_timeout`] to give up after a timeout.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let handle = app.handle().clone();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       let (groups, _child) = handle.shell().sidecar("dev-server")
+    ///         .unwrap()
+    ///         .spawn_until_match(r"listening on (http://\S+)")
+    ///         .await
+    ///         .expect("dev-server didn't start");
+    ///       println!("dev server running at {}", groups[0]);
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn spawn_until_match(
+        mut self,
+        pattern: &str,
+    ) -> crate::Result<(Vec<String>, CommandChild)> {
+        let regex = regex::Regex::new(pattern)?;
+        self.raw_out = false;
+        let (mut rx, child) = self.spawn()?;
+        let mut guard = ChildGuard(Some(child));
+
+        while let Some(event) = rx.recv().await {
+            let lines = match event {
+                CommandEvent::Stdout(line) => vec![line],
+                CommandEvent::StdoutBatch(lines) => lines,
+                _ => continue,
+            };
+            for line in lines {
+                let line = String::from_utf8_lossy(trim_line_ending(&line)).into_owned();
+                if let Some(captures) = regex.captures(&line) {
+                    let groups = captures
+                        .iter()
+                        .skip(1)
+                        .map(|group| group.map_or_else(String::new, |m| m.as_str().to_string()))
+                        .collect();
+                    tauri::async_runtime::spawn(async move { while rx.recv().await.is_some() {} });
+                    let child = guard.0.take().expect("the child is only taken once");
+                    return Ok((groups, child));
+                }
+            }
+        }
+        Err(crate::Error::NoMatch(pattern.to_string()))
+    }
+
+    /// Like [`Self::spawn_until_match`], giving up after `timeout`.
+    ///
+    /// If no line of stdout matches the `pattern` in time the process is killed,
+    /// and [`crate::Error::MatchTimedOut`] is returned.
+    pub async fn spawn_until_match_timeout(
+        self,
+        pattern: &str,
+        timeout: Duration,
+    ) -> crate::Result<(Vec<String>, CommandChild)> {
+        // dropping the future kills the process
+        tokio::time::timeout(timeout, self.spawn_until_match(pattern))
+            .await
+            .unwrap_or_else(|_| Err(crate::Error::MatchTimedOut(pattern.to_string())))
+    }
+
+    /// Spawns the command, returning a [`Session`] bundling its event stream, stdin and exit status.
+    ///
+    /// This is a single handle for a long-lived sidecar, which kills the process when dropped unless detached.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let handle = app.handle().clone();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       let mut session = handle.shell().sidecar("rpc-server").unwrap().start().unwrap();
+    ///       session.write(b"{\"method\":\"ping\"}\n").unwrap();
+    ///       if let Some(CommandEvent::Stdout(response)) = session.recv().await {
+    ///         println!("response: {}", String::from_utf8_lossy(&response));
+    ///       }
+    ///       // the sidecar is killed here
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn start(self) -> crate::Result<Session> {
+        let (events, child) = self.spawn()?;
+        Ok(Session {
+            events,
+            child: ChildGuard(Some(child)),
+        })
+    }
+
+    /// Spawns the command, returning an independent handle to its stdin alongside the event receiver.
+    ///
+    /// This is the shape of a request/response channel with a long-lived sidecar:
+    /// requests are written to the [`ChildStdin`] while responses are read from the receiver,
+    /// without borrowing the [`CommandChild`] mutably. See [`CommandChild::try_clone_stdin`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::io::Write;
+    /// use tauri_plugin_shell::{process::CommandEvent, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let handle = app.handle().clone();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       let (mut stdin, mut rx, _child) = handle.shell().sidecar("rpc-server")
+    ///         .unwrap()
+    ///         .spawn_duplex()
+    ///         .expect("Failed to spawn rpc-server");
+    ///
+    ///       stdin.write_all(b"{\"method\":\"ping\"}\n").unwrap();
+    ///       while let Some(event) = rx.recv().await {
+    ///         if let CommandEvent::Stdout(response) = event {
+    ///           println!("response: {}", String::from_utf8_lossy(&response));
+    ///         }
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_duplex(self) -> crate::Result<(ChildStdin, Receiver<CommandEvent>, CommandChild)> {
+        let (rx, child) = self.spawn()?;
+        let stdin = child.try_clone_stdin()?;
+        Ok((stdin, rx, child))
+    }
+
+    /// Spawns the command and restarts it whenever it exits with a failure.
+    ///
+    /// The events of every run are forwarded to the returned receiver, including the
+    /// [`CommandEvent::Terminated`] event of each run. The command is not restarted if it exits successfully
+    /// or after [`Supervisor::stop`] is called, in which case the receiver is closed.
+    ///
+    /// Restarts are delayed according to [`Self::restart_backoff`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let handle = app.handle().clone();
+    ///     tauri::async_runtime::spawn(async move {
+    ///       let (mut rx, supervisor) = handle.shell().sidecar("server")
+    ///         .unwrap()
+    ///         .restart_backoff(Duration::from_millis(500), Duration::from_secs(30), 2.0)
+    ///         .spawn_supervised()
+    ///         .expect("Failed to spawn server");
+    ///
+    ///       while let Some(event) = rx.recv().await {
+    ///         println!("{event:?}");
+    ///       }
+    ///     });
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn spawn_supervised(self) -> crate::Result<(Receiver<CommandEvent>, Supervisor)> {
+        let backoff = self.restart_backoff.unwrap_or_default();
+        let source = self.clone();
+        let mut command: StdCommand = self.into();
+
+        let (mut child_rx, child) = spawn_child(&source, &mut command)?;
+        let state = Arc::new(SupervisorState {
+            stopped: AtomicBool::new(false),
+            child: Mutex::new(Some(child)),
+        });
+        let state_ = state.clone();
+
+        let (tx, rx) = channel(source.event_buffer);
+
+        tauri::async_runtime::spawn(async move {
+            let mut failures = 0;
+            loop {
+                let started = Instant::now();
+                let mut success = false;
+                while let Some(event) = child_rx.recv().await {
+                    if let CommandEvent::Terminated(payload) = &event {
+                        success = payload.code == Some(0);
+                    }
+                    if tx.send(event).await.is_err() {
+                        // nobody is listening anymore
+                        let _ = state_.stop();
+                        return;
+                    }
+                }
+
+                if success || state_.stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                // the child was healthy for long enough, this is not a crash loop
+                if started.elapsed() >= backoff.max {
+                    failures = 0;
+                }
+                failures += 1;
+                tokio::time::sleep(backoff.delay(failures)).await;
+
+                loop {
+                    if state_.stopped.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    match spawn_child(&source, &mut command) {
+                        Ok((rx, child)) => {
+                            let mut current = state_.child.lock().unwrap();
+                            // stopped while spawning, after `stop` found no child to kill
+                            if state_.stopped.load(Ordering::Relaxed) {
+                                let _ = child.kill();
+                                return;
+                            }
+                            current.replace(child);
+                            child_rx = rx;
+                            break;
+                        }
+                        Err(e) => {
+                            if tx.send(CommandEvent::Error(e.to_string())).await.is_err() {
+                                return;
+                            }
+                            failures += 1;
+                            tokio::time::sleep(backoff.delay(failures)).await;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok((rx, Supervisor { state }))
+    }
+
+    /// Executes a command as a child process, waiting for it to finish and collecting its exit status.
+    /// Stdin, stdout and stderr are ignored. The output is discarded with [`Self::null_stdio`] unless it's
+    /// redirected to a file or teed, so no reader tasks are spawned.
+    ///
+    /// Dropping the returned future before it completes, e.g. when it loses a `select!`, kills the process.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let status = tauri::async_runtime::block_on(async move { app.shell().command("which").args(["ls"]).status().await.unwrap() });
+    ///     println!("`which` finished with status: {:?}", status.code());
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn status(self) -> crate::Result<ExitStatus> {
+        let (rx, child) = self.discard_output().spawn()?;
+        let _guard = ChildGuard(Some(child));
+        Ok(collect_status(rx).await)
+    }
+
+    /// Executes the command as a child process like [`Self::status`], giving up after `timeout`.
+    ///
+    /// Returns `None` if the process doesn't exit in time, after killing it and waiting for it to exit.
+    /// Dropping the returned future before it completes kills the process, like with [`Self::status`].
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let status = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("pg_isready").status_with_timeout(Duration::from_secs(2)).await
+    ///     })?;
+    ///     match status {
+    ///       Some(status) if status.success() => println!("the database is ready"),
+    ///       Some(_) => println!("the database is not ready"),
+    ///       None => println!("the health check timed out"),
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn status_with_timeout(self, timeout: Duration) -> crate::Result<Option<ExitStatus>> {
+        let (rx, child) = self.discard_output().spawn()?;
+        let exit = child.subscribe_exit();
+        let mut guard = ChildGuard(Some(child));
+        match tokio::time::timeout(timeout, collect_status(rx)).await {
+            Ok(status) => Ok(Some(status)),
+            Err(_) => {
+                let child = guard.0.take().expect("the guard holds the child");
+                // dropping the receiver stops the readers once the pipes are closed by the kill
+                let _ = child.kill();
+                exit.await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Spawns the command like [`Self::status`], returning a future resolving to its exit status alongside the child.
+    ///
+    /// The child can be used to stop the process while the future is pending, e.g. with [`CommandChild::kill`],
+    /// in which case the future resolves to the status of the killed process.
+    /// Unlike [`Self::status`], dropping the future doesn't kill the process.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let (status, child) = app.shell().command("fsck").args(["-n", "/dev/sda1"]).status_cancellable()?;
+    ///     // e.g. when the user clicks cancel
+    ///     child.kill()?;
+    ///     let status = tauri::async_runtime::block_on(status)?;
+    ///     println!("`fsck` finished with status: {:?}", status.code());
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn status_cancellable(
+        self,
+    ) -> crate::Result<(
+        impl std::future::Future<Output = crate::Result<ExitStatus>> + Send + 'static,
+        CommandChild,
+    )> {
+        let (rx, child) = self.discard_output().spawn()?;
+        Ok((async move { Ok(collect_status(rx).await) }, child))
+    }
+
+    /// Executes the command as a child process and waits for it to finish, collecting its exit status.
+    ///
+    /// Unlike [`Self::status`], stdin, stdout and stderr are redirected to the null device,
+    /// so no pipes, event channel or reader tasks are created.
+    /// This blocks the current thread until the process exits.
+    ///
+    /// # Examples
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let status = app.shell().command("which").args(["ls"]).status_blocking().unwrap();
+    ///     println!("`which` finished with status: {:?}", status.code());
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub fn status_blocking(self) -> crate::Result<ExitStatus> {
+        let mut command: StdCommand = self.into();
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+        Ok(command.status()?.into())
+    }
+
+    /// Executes the command as a child process, waiting for it to finish and collecting all of its output.
+    /// Stdin is ignored.
+    ///
+    /// The output is collected byte for byte as the process wrote it, including its line endings.
+    ///
+    /// Dropping the returned future before it completes, e.g. when it loses a `select!`, kills the process.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let output = tauri::async_runtime::block_on(async move { app.shell().command("echo").args(["TAURI"]).output().await.unwrap() });
+    ///     assert!(output.status.success());
+    ///     assert_eq!(String::from_utf8(output.stdout).unwrap(), "TAURI\n");
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn output(self) -> crate::Result<Output> {
+        let mut output = OutputCollector::new(&self);
+        let (mut rx, child) = self.spawn()?;
+        let _guard = ChildGuard(Some(child));
+
+        while let Some(event) = rx.recv().await {
+            output.push(event);
+        }
+        Ok(output.finish())
+    }
+
+    /// Executes the command as a child process like [`Self::output`], giving up after `timeout`.
+    ///
+    /// If the process doesn't finish in time it is killed, and [`crate::Error::TimedOut`] is returned
+    /// with the output collected until then and the status of the killed process.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tauri_plugin_shell::{Error, ShellExt};
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let result = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("ping").args(["tauri.app"]).output_with_timeout(Duration::from_secs(5)).await
+    ///     });
+    ///     match result {
+    ///       Ok(output) => println!("ping finished with status {:?}", output.status.code()),
+    ///       Err(Error::TimedOut(output)) => println!("ping timed out, partial output: {:?}", output.stdout),
+    ///       Err(e) => println!("failed to run ping: {e}"),
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn output_with_timeout(self, timeout: Duration) -> crate::Result<Output> {
+        let mut output = OutputCollector::new(&self);
+        let (mut rx, child) = self.spawn()?;
+        let mut guard = ChildGuard(Some(child));
+
+        let finished = tokio::time::timeout(timeout, async {
+            while let Some(event) = rx.recv().await {
+                output.push(event);
+            }
+        })
+        .await;
+        if finished.is_ok() {
+            return Ok(output.finish());
+        }
+
+        let child = guard.0.take().expect("the guard holds the child");
+        let exit = child.subscribe_exit();
+        // no-op if the process exited right after the deadline
+        let _ = child.kill();
+        // don't wait for the pipes to close, a grandchild could still hold them open
+        output.status = exit.await?;
+        while let Ok(event) = rx.try_recv() {
+            if !matches!(event, CommandEvent::Terminated(_)) {
+                output.push(event);
+            }
+        }
+        Err(crate::Error::TimedOut(Box::new(output.finish())))
+    }
+
+    /// Executes the command as a child process, streaming its stdout to `stdout` and collecting its stderr in memory.
+    /// Stdin is ignored.
+    ///
+    /// The output is written exactly as received. Only the last `stderr_cap` bytes of stderr are kept,
+    /// and they're returned in [`crate::Error::NonZeroExit`] if the process doesn't exit successfully,
+    /// so a failure can be reported without buffering the whole output.
+    /// Dropping the returned future before it completes kills the process.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let file = std::fs::File::create("dump.sql")?;
+    ///     let result = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("pg_dump").args(["mydb"]).output_to(file, 64 * 1024).await
+    ///     });
+    ///     if let Err(e) = result {
+    ///       println!("pg_dump failed: {e}");
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn output_to<W: Write + Send>(
+        self,
+        mut stdout: W,
+        stderr_cap: usize,
+    ) -> crate::Result<ExitStatus> {
+        let exit_code_map = self.exit_code_map.clone();
+        let (mut rx, child) = self.spawn()?;
+        let _guard = ChildGuard(Some(child));
+
+        let mut status = ExitStatus {
+            code: None,
+            signal: None,
+        };
+        let mut stderr = Vec::new();
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Terminated(payload) => {
+                    status = ExitStatus::from_payload(&payload);
+                }
+                CommandEvent::Stdout(chunk) => {
+                    stdout.write_all(&chunk)?;
+                }
+                CommandEvent::StdoutBatch(chunks) => {
+                    for chunk in chunks {
+                        stdout.write_all(&chunk)?;
+                    }
+                }
+                CommandEvent::Stderr(chunk) => {
+                    stderr.extend(chunk);
+                    if stderr.len() > stderr_cap {
+                        stderr.drain(..stderr.len() - stderr_cap);
+                    }
+                }
+                CommandEvent::StderrBatch(chunks) => {
+                    stderr.extend(chunks.concat());
+                    if stderr.len() > stderr_cap {
+                        stderr.drain(..stderr.len() - stderr_cap);
+                    }
+                }
+                CommandEvent::Error(_) | CommandEvent::FirstOutput { .. } => {}
+            }
+        }
+        stdout.flush()?;
+
+        if status.success() {
+            Ok(status)
+        } else {
+            Err(exit_error(&exit_code_map, status.code, stderr))
+        }
+    }
+
+    /// Executes the command as a child process like [`Self::output`], failing if it doesn't exit successfully.
+    ///
+    /// Exit codes registered with [`Self::exit_code_map`] produce a [`crate::Error::KnownExit`] with their meaning,
+    /// other failures a [`crate::Error::NonZeroExit`] with the collected stderr.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use std::collections::HashMap;
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let result = tauri::async_runtime::block_on(async move {
+    ///       app.shell()
+    ///         .command("rsync")
+    ///         .args(["-a", "src/", "dest/"])
+    ///         .exit_code_map(HashMap::from([
+    ///           (23, "partial transfer due to error".to_string()),
+    ///           (24, "partial transfer due to vanished source files".to_string()),
+    ///         ]))
+    ///         .output_checked()
+    ///         .await
+    ///     });
+    ///     if let Err(e) = result {
+    ///       println!("rsync failed: {e}");
+    ///     }
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn output_checked(self) -> crate::Result<Output> {
+        let exit_code_map = self.exit_code_map.clone();
+        let output = self.output().await?;
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(exit_error(
+                &exit_code_map,
+                output.status.code,
+                output.stderr,
+            ))
+        }
+    }
+
+    /// Executes the command as a child process, collecting its stdout as lossily decoded UTF-8 lines.
+    /// Stdin and stderr are ignored.
+    ///
+    /// The line terminators are removed. The exit status of the process isn't checked,
+    /// use [`Self::output`] when it matters. Dropping the returned future before it completes kills the process.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use tauri_plugin_shell::ShellExt;
+    /// tauri::Builder::default()
+    ///   .setup(|app| {
+    ///     let branches = tauri::async_runtime::block_on(async move {
+    ///       app.shell().command("git").args(["branch", "--format=%(refname:short)"]).stdout_lines().await
+    ///     })?;
+    ///     println!("branches: {branches:?}");
+    ///     Ok(())
+    ///   });
+    /// ```
+    pub async fn stdout_lines(self) -> crate::Result<Vec<String>> {
+        let lines = self.stdout_lines_bytes().await?;
+        Ok(lines
+            .into_iter()
+            .map(|line| String::from_utf8_lossy(&line).into_owned())
+            .collect())
+    }
+
+    /// Executes the command as a child process, collecting its stdout as lines of raw bytes.
+    /// Stdin and stderr are ignored.
+    ///
+    /// The binary-safe variant of [`Self::stdout_lines`]. The output is always split in lines,
+    /// even if [`Self::set_raw_out`] is enabled.
+    pub async fn stdout_lines_bytes(mut self) -> crate::Result<Vec<Vec<u8>>> {
+        self.raw_out = false;
+        let (mut rx, child) = self.spawn()?;
+        let _guard = ChildGuard(Some(child));
+
+        let mut lines = Vec::new();
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => lines.push(trim_line_ending(&line).to_vec()),
+                CommandEvent::StdoutBatch(batch) => {
+                    lines.extend(batch.iter().map(|line| trim_line_ending(line).to_vec()))
+                }
+                _ => {}
+            }
+        }
+        Ok(lines)
+    }
+}
+
+/// Builds the error for a process that didn't exit successfully.
+fn exit_error(
+    exit_code_map: &HashMap<i32, String>,
+    code: Option<i32>,
+    stderr: Vec<u8>,
+) -> crate::Error {
+    match code.and_then(|code| exit_code_map.get(&code).map(|meaning| (code, meaning))) {
+        Some((code, meaning)) => crate::Error::KnownExit {
+            code,
+            meaning: meaning.clone(),
+        },
+        None => crate::Error::NonZeroExit { code, stderr },
+    }
+}
+
+/// Removes the `\n` or `\r\n` terminator of a line.
+fn trim_line_ending(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(&[NEWLINE_BYTE]).unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Collects the output of a stream for [`Command::output`], keeping only the last `limit` bytes if set.
+struct OutputBuffer {
+    data: Vec<u8>,
+    limit: Option<usize>,
+    total: usize,
+}
+
+impl OutputBuffer {
+    fn new(capacity: usize, limit: Option<usize>) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+            limit,
+            total: 0,
+        }
+    }
+
+    /// Appends an output chunk, which keeps the line ending it was read with.
+    fn push(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+        self.total += chunk.len();
+        if let Some(limit) = self.limit {
+            // only shift the data once the buffer doubled, so each byte is moved a bounded number of times
+            if self.data.len() > limit.saturating_mul(2) {
+                self.data.drain(..self.data.len() - limit);
+            }
+        }
+    }
+
+    /// Returns the retained data and the total number of bytes pushed.
+    fn finish(mut self) -> (Vec<u8>, usize) {
+        if let Some(limit) = self.limit {
+            if self.data.len() > limit {
+                self.data.drain(..self.data.len() - limit);
+            }
+        }
+        (self.data, self.total)
+    }
+}
+
+/// Collects the events of a process into an [`Output`], see [`Command::output`].
+struct OutputCollector {
+    status: ExitStatus,
+    stdout: OutputBuffer,
+    stderr: OutputBuffer,
+}
+
+impl OutputCollector {
+    fn new(command: &Command) -> Self {
+        let (stdout_capacity, stderr_capacity) = command.output_capacity;
+        Self {
+            status: ExitStatus {
+                code: None,
+                signal: None,
+            },
+            stdout: OutputBuffer::new(stdout_capacity, command.keep_last_bytes),
+            stderr: OutputBuffer::new(stderr_capacity, command.keep_last_bytes),
+        }
+    }
+
+    fn push(&mut self, event: CommandEvent) {
+        match event {
+            CommandEvent::Terminated(payload) => {
+                self.status = ExitStatus::from_payload(&payload);
+            }
+            CommandEvent::Stdout(line) => self.stdout.push(&line),
+            CommandEvent::Stderr(line) => self.stderr.push(&line),
+            CommandEvent::StdoutBatch(lines) => {
+                for line in lines {
+                    self.stdout.push(&line);
+                }
+            }
+            CommandEvent::StderrBatch(lines) => {
+                for line in lines {
+                    self.stderr.push(&line);
+                }
+            }
+            CommandEvent::Error(_) | CommandEvent::FirstOutput { .. } => {}
+        }
+    }
+
+    fn finish(self) -> Output {
+        let (stdout, stdout_total) = self.stdout.finish();
+        let (stderr, stderr_total) = self.stderr.finish();
+        Output {
+            status: self.status,
+            stdout,
+            stderr,
+            stdout_total,
+            stderr_total,
+        }
+    }
+}
+
+/// Waits for the [`CommandEvent::Terminated`] event, discarding the output.
+async fn collect_status(mut rx: Receiver<CommandEvent>) -> ExitStatus {
+    let mut status = ExitStatus {
+        code: None,
+        signal: None,
+    };
+    #[allow(clippy::collapsible_match)]
+    while let Some(event) = rx.recv().await {
+        if let CommandEvent::Terminated(payload) = event {
+            status = ExitStatus::from_payload(&payload);
+        }
+    }
+    status
+}
+
+/// Kills the child when dropped, so cancelling a future awaiting its completion also stops the process.
+#[derive(Debug)]
+struct ChildGuard(Option<CommandChild>);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(child) = self.0.take() {
+            // no-op if the process already exited
+            let _ = child.kill();
+        }
+    }
+}
+
+/// Options applied by the pipe readers to the output of the child process.
+#[derive(Debug, Clone, Default)]
+struct ReaderOptions {
+    raw_out: bool,
+    /// The capacity of the reader buffer in raw mode, which bounds the size of the events.
+    raw_chunk_size: usize,
+    /// Emits the line read so far once it reaches this size.
+    max_line_len: Option<usize>,
+    /// Decodes the output to UTF-8 from this encoding.
+    encoding: Option<&'static Encoding>,
+    line_prefix: Option<Vec<u8>>,
+    /// Splits the output on this byte instead of line endings.
+    delimiter: Option<u8>,
+    normalize_newlines: bool,
+    measure_first_output: bool,
+    /// Set for each spawned process when `measure_first_output` is enabled.
+    first_output: Option<Arc<FirstOutput>>,
+    drain_on_kill: bool,
+    /// Set for each spawned process when `drain_on_kill` is disabled.
+    killed: Option<Arc<AtomicBool>>,
+    max_events_per_sec: Option<u32>,
+    /// Set for each pipe with a tee file.
+    tee: Option<Arc<File>>,
+    /// Reads the pipes with blocking reads on a thread per stream instead of on the async runtime,
+    /// see [`Command::spawn_sync_channel`].
+    blocking: bool,
+}
+
+/// Tracks the first output of a process, shared by its stdout and stderr readers.
+#[derive(Debug)]
+struct FirstOutput {
+    spawned_at: Instant,
+    reported: AtomicBool,
+}
+
+impl ReaderOptions {
+    /// Whether the process was killed and its remaining output must be discarded.
+    fn discard(&self) -> bool {
+        self.killed
+            .as_ref()
+            .is_some_and(|killed| killed.load(Ordering::Relaxed))
+    }
+
+    /// Emits the [`CommandEvent::FirstOutput`] event if this is the first output of the process.
+    async fn report_first_output(&self, tx: &EventSender) {
+        if let Some(first_output) = &self.first_output {
+            if !first_output.reported.swap(true, Ordering::Relaxed) {
+                let _ = tx
+                    .send_async(CommandEvent::FirstOutput {
+                        latency: first_output.spawned_at.elapsed(),
+                    })
+                    .await;
+            }
+        }
+    }
+
+    /// Writes a chunk read from the pipe to the tee file, reporting failures as [`CommandEvent::Error`] events.
+    async fn tee(&self, tx: &EventSender, chunk: &[u8]) {
+        if let Some(file) = &self.tee {
+            let written = if self.blocking {
+                (&**file).write_all(chunk)
+            } else {
+                // the file might be a FIFO or on a slow mount, so the write must not block the runtime
+                let (file, chunk) = (file.clone(), chunk.to_vec());
+                tauri::async_runtime::spawn_blocking(move || (&*file).write_all(&chunk))
+                    .await
+                    .unwrap_or_else(|e| Err(std::io::Error::other(e.to_string())))
+            };
+            if let Err(e) = written {
+                let _ = tx
+                    .send_async(CommandEvent::Error(format!(
+                        "failed to write the output to the tee file: {e}"
+                    )))
+                    .await;
+            }
+        }
+    }
+
+    /// Creates the decoder of the configured encoding for a pipe, `None` if the output is emitted as is.
+    fn decoder(&self) -> Option<Decoder> {
+        self.encoding.map(Encoding::new_decoder)
+    }
+
+    /// Transforms a chunk read from the pipe before it is emitted as an event, nothing is emitted if it's empty.
+    ///
+    /// `after_cr` tracks whether the last chunk ended with a `\r`, see [`normalize_newlines`].
+    fn transform(
+        &self,
+        decoder: &mut Option<Decoder>,
+        after_cr: &mut bool,
+        mut chunk: Vec<u8>,
+    ) -> Vec<u8> {
+        if let Some(decoder) = decoder {
+            chunk = decode(decoder, &chunk, false);
+        }
+        if self.normalize_newlines {
+            chunk = normalize_newlines(chunk, after_cr);
+        }
+        match &self.line_prefix {
+            Some(prefix) if !chunk.is_empty() => {
+                let mut prefixed = Vec::with_capacity(prefix.len() + chunk.len());
+                prefixed.extend_from_slice(prefix);
+                prefixed.extend(chunk);
+                prefixed
+            }
+            _ => chunk,
+        }
+    }
+}
+
+/// Decodes a chunk to UTF-8, keeping an incomplete sequence at its end in the decoder until the next chunk.
+fn decode(decoder: &mut Decoder, chunk: &[u8], last: bool) -> Vec<u8> {
+    let capacity = decoder
+        .max_utf8_buffer_length(chunk.len())
+        .unwrap_or(chunk.len());
+    let mut text = String::with_capacity(capacity);
+    let _ = decoder.decode_to_string(chunk, &mut text, last);
+    text.into_bytes()
+}
+
+/// Emits the sequence left incomplete at the end of the output, if any, once the pipe is closed.
+async fn flush_decoder<F: Fn(Bytes) -> CommandEvent>(
+    tx: &EventSender,
+    wrapper: F,
+    decoder: Option<Decoder>,
+) {
+    if let Some(mut decoder) = decoder {
+        let rest = decode(&mut decoder, &[], true);
+        if !rest.is_empty() {
+            let _ = tx.send_async(wrapper(rest.into())).await;
+        }
+    }
+}
+
+/// Converts `\r\n` and lone `\r` line endings to `\n`.
+///
+/// A `\r` ending the chunk is converted right away, so `after_cr` is set for the `\n` of a `\r\n`
+/// split across chunks to be dropped from the next one instead of being emitted as another line ending.
+fn normalize_newlines(chunk: Vec<u8>, after_cr: &mut bool) -> Vec<u8> {
+    let split_crlf = std::mem::replace(after_cr, chunk.last() == Some(&b'\r'))
+        && chunk.first() == Some(&NEWLINE_BYTE);
+    let chunk = if split_crlf { &chunk[1..] } else { &chunk[..] };
+    if !chunk.contains(&b'\r') {
+        return chunk.to_vec();
+    }
+    let mut normalized = Vec::with_capacity(chunk.len());
+    let mut bytes = chunk.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\r' if bytes.peek() == Some(&&NEWLINE_BYTE) => {}
+            b'\r' => normalized.push(NEWLINE_BYTE),
+            byte => normalized.push(byte),
+        }
+    }
+    normalized
+}
+
+async fn read_raw_bytes<R: AsyncBufRead + Unpin, F: Fn(Bytes) -> CommandEvent>(
+    mut reader: R,
+    tx: EventSender,
+    wrapper: F,
+    options: &ReaderOptions,
+) {
+    let mut decoder = options.decoder();
+    let mut after_cr = false;
+    loop {
+        let result = reader.fill_buf().await;
         match result {
             Ok(buf) => {
-                let length = buf.len();
-                if length == 0 {
+                let buf = buf.to_vec();
+                if buf.is_empty() {
+                    flush_decoder(&tx, wrapper, decoder).await;
+                    break;
+                }
+                reader.consume(buf.len());
+                if options.discard() {
+                    break;
+                }
+                options.report_first_output(&tx).await;
+                options.tee(&tx, &buf).await;
+                let chunk = options.transform(&mut decoder, &mut after_cr, buf);
+                // nobody is listening anymore
+                if !chunk.is_empty() && tx.send_async(wrapper(chunk.into())).await.is_err() {
+                    break;
+                }
+            }
+            // the read was interrupted by a signal before any data was read, try again
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                let _ = tx.send_async(CommandEvent::Error(e.to_string())).await;
+                break;
+            }
+        }
+    }
+}
+
+async fn read_line<R: AsyncBufRead + Unpin, F: Fn(Bytes) -> CommandEvent>(
+    mut reader: R,
+    tx: EventSender,
+    wrapper: F,
+    options: &ReaderOptions,
+) {
+    let mut decoder = options.decoder();
+    let mut after_cr = false;
+    // kept when the read is interrupted, since it might already hold the start of the line
+    let mut buf = Vec::new();
+    loop {
+        let result = match options.max_line_len {
+            Some(max) => {
+                let mut reader = (&mut reader).take((max - buf.len()) as u64);
+                read_segment(&mut reader, options, &mut buf).await
+            }
+            None => read_segment(&mut reader, options, &mut buf).await,
+        };
+        match result {
+            Ok(_) => {
+                if buf.is_empty() {
+                    flush_decoder(&tx, wrapper, decoder).await;
+                    break;
+                }
+                if options.discard() {
+                    break;
+                }
+                options.report_first_output(&tx).await;
+                options.tee(&tx, &buf).await;
+                let chunk =
+                    options.transform(&mut decoder, &mut after_cr, std::mem::take(&mut buf));
+                if !chunk.is_empty() && tx.send_async(wrapper(chunk.into())).await.is_err() {
                     break;
                 }
-                let tx_ = tx.clone();
-                let _ = block_on_task(async move { tx_.send(wrapper(buf.to_vec())).await });
-                reader.consume(length);
             }
-            Err(e) => {
-                let tx_ = tx.clone();
-                let _ = block_on_task(
-                    async move { tx_.send(CommandEvent::Error(e.to_string())).await },
-                );
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                let _ = tx.send_async(CommandEvent::Error(e.to_string())).await;
+                break;
+            }
+        }
+    }
+}
+
+/// Reads the next line, or the next segment split on the configured delimiter, into `buf`.
+async fn read_segment<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    options: &ReaderOptions,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    match options.delimiter {
+        Some(delimiter) => reader.read_until(delimiter, buf).await,
+        None => read_until_line_ending(reader, buf).await,
+    }
+}
+
+/// Reads into `buf` until a newline, or a carriage return if there is no newline in the buffered output.
+///
+/// This is [`tauri::utils::io::read_line`] for async readers.
+async fn read_until_line_ending<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        let (done, used) = match available
+            .iter()
+            .position(|&byte| byte == NEWLINE_BYTE)
+            .or_else(|| available.iter().position(|&byte| byte == b'\r'))
+        {
+            Some(i) => (true, i + 1),
+            None => (false, available.len()),
+        };
+        buf.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        read += used;
+        if done || used == 0 {
+            return Ok(read);
+        }
+    }
+}
+
+/// The read end of an output pipe of the child.
+enum PipeSource {
+    Pipe(PipeReader),
+    /// The master side of the pseudo-terminal, see [`Command::spawn_pty`].
+    #[cfg(all(unix, feature = "pty"))]
+    Pty(File),
+}
+
+impl PipeSource {
+    /// Opens the pipe for a reader running on the async runtime, or on its own thread if `blocking` is set.
+    fn open(self, blocking: bool) -> std::io::Result<Box<dyn AsyncRead + Send + Unpin>> {
+        Ok(match self {
+            Self::Pipe(reader) if blocking => Box::new(BlockingReader(reader)),
+            Self::Pipe(reader) => Box::new(async_pipe(reader)?),
+            #[cfg(all(unix, feature = "pty"))]
+            Self::Pty(master) if blocking => Box::new(pty::PtyReader(BlockingReader(master))),
+            // the master is shared with the stdin writer, which expects it to stay in blocking mode,
+            // so it's read on the blocking thread pool
+            #[cfg(all(unix, feature = "pty"))]
+            Self::Pty(master) => Box::new(pty::PtyReader(tokio::fs::File::from_std(master))),
+        })
+    }
+}
+
+/// Reads with blocking reads on the current thread, for the readers running on their own threads.
+struct BlockingReader<R>(R);
+
+impl<R: Read + Unpin> AsyncRead for BlockingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let read = self.0.read(buf.initialize_unfilled())?;
+        buf.advance(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A running pipe reader, see [`spawn_pipe_reader`].
+enum ReaderHandle {
+    Task(TaskHandle<()>),
+    Thread(JoinHandle<()>),
+}
+
+impl ReaderHandle {
+    /// Waits for the reader to finish.
+    fn join(self) {
+        match self {
+            Self::Task(task) => {
+                let _ = block_on_task(task);
+            }
+            Self::Thread(thread) => {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Spawns the reader of a pipe, a task on the async runtime or a thread if [`ReaderOptions::blocking`] is set.
+fn spawn_pipe_reader<F: Fn(Bytes) -> CommandEvent + Send + Copy + 'static>(
+    tx: EventSender,
+    pipe: PipeSource,
+    wrapper: F,
+    options: ReaderOptions,
+) -> ReaderHandle {
+    let blocking = options.blocking;
+    let read = async move {
+        // registered with the runtime when it's not blocking, so it's opened by the reader
+        let pipe_reader = match pipe.open(options.blocking) {
+            Ok(pipe_reader) => pipe_reader,
+            Err(e) => {
+                let _ = tx.send_async(CommandEvent::Error(e.to_string())).await;
+                return;
+            }
+        };
+        let reader = if options.raw_out {
+            BufReader::with_capacity(options.raw_chunk_size, pipe_reader)
+        } else {
+            BufReader::new(pipe_reader)
+        };
+
+        match options.max_events_per_sec {
+            Some(rate) if options.blocking => {
+                // batched on a separate thread so pending output can be flushed while the read blocks
+                let (chunk_tx, chunk_rx) = mpsc::sync_channel(1);
+                let limiter = spawn_thread(move || {
+                    rate_limit_blocking(chunk_rx, tx, Duration::from_secs(1) / rate)
+                });
+                let chunk_tx = EventSender::Sync(Arc::new(chunk_tx));
+                read_pipe(reader, chunk_tx, wrapper, &options).await;
+                let _ = limiter.join();
+            }
+            Some(rate) => {
+                // batched on a separate task so pending output can be flushed while the read waits
+                let (chunk_tx, chunk_rx) = channel(1);
+                let limiter = spawn_task(rate_limit(chunk_rx, tx, Duration::from_secs(1) / rate));
+                read_pipe(reader, EventSender::Async(chunk_tx), wrapper, &options).await;
+                let _ = limiter.await;
+            }
+            None => read_pipe(reader, tx, wrapper, &options).await,
+        }
+    };
+    if blocking {
+        ReaderHandle::Thread(spawn_thread(move || block_on_thread(read)))
+    } else {
+        ReaderHandle::Task(spawn_task(read))
+    }
+}
+
+/// Registers the read end of a pipe with the async runtime.
+#[cfg(unix)]
+fn async_pipe(reader: PipeReader) -> std::io::Result<tokio::net::unix::pipe::Receiver> {
+    tokio::net::unix::pipe::Receiver::from_owned_fd(reader.into())
+}
+
+/// Wraps the read end of a pipe in an async reader.
+///
+/// Tokio can't poll anonymous pipes on Windows, so the reads run on its blocking thread pool.
+#[cfg(windows)]
+fn async_pipe(reader: PipeReader) -> std::io::Result<tokio::fs::File> {
+    Ok(tokio::fs::File::from_std(File::from(
+        std::os::windows::io::OwnedHandle::from(reader),
+    )))
+}
+
+async fn read_pipe<R: AsyncBufRead + Unpin, F: Fn(Bytes) -> CommandEvent>(
+    reader: R,
+    tx: EventSender,
+    wrapper: F,
+    options: &ReaderOptions,
+) {
+    if options.raw_out {
+        read_raw_bytes(reader, tx, wrapper, options).await;
+    } else {
+        read_line(reader, tx, wrapper, options).await;
+    }
+}
+
+/// Emits at most one output event per window, coalescing the output received in between into a batch,
+/// see [`Command::max_events_per_sec`].
+struct RateLimiter {
+    pending: Vec<CommandEvent>,
+    next_emit: Instant,
+    window: Duration,
+}
+
+impl RateLimiter {
+    fn new(window: Duration) -> Self {
+        Self {
+            pending: Vec::new(),
+            next_emit: Instant::now(),
+            window,
+        }
+    }
+
+    /// When the pending output must be flushed, `None` if there is none.
+    fn deadline(&self) -> Option<Instant> {
+        (!self.pending.is_empty()).then_some(self.next_emit)
+    }
+
+    /// Takes an event of the reader, returning the events to emit now.
+    fn push(&mut self, event: CommandEvent) -> [Option<CommandEvent>; 2] {
+        match event {
+            CommandEvent::Stdout(_) | CommandEvent::Stderr(_) => {
+                let now = Instant::now();
+                if self.pending.is_empty() && now >= self.next_emit {
+                    self.next_emit = now + self.window;
+                    [Some(event), None]
+                } else {
+                    self.pending.push(event);
+                    [None, None]
+                }
+            }
+            event => [self.flush(), Some(event)],
+        }
+    }
+
+    /// Flushes the pending output once the window ended.
+    fn window_ended(&mut self) -> Option<CommandEvent> {
+        self.next_emit = Instant::now() + self.window;
+        self.flush()
+    }
+
+    /// Coalesces the pending output into a batch, `None` if there is none.
+    fn flush(&mut self) -> Option<CommandEvent> {
+        match self.pending.len() {
+            0 => None,
+            1 => Some(self.pending.remove(0)),
+            _ => {
+                let stdout = matches!(self.pending[0], CommandEvent::Stdout(_));
+                let chunks = self
+                    .pending
+                    .drain(..)
+                    .filter_map(|event| match event {
+                        CommandEvent::Stdout(chunk) | CommandEvent::Stderr(chunk) => Some(chunk),
+                        _ => None,
+                    })
+                    .collect();
+                Some(if stdout {
+                    CommandEvent::StdoutBatch(chunks)
+                } else {
+                    CommandEvent::StderrBatch(chunks)
+                })
+            }
+        }
+    }
+}
+
+/// Forwards the events of `rx` to `tx` through a [`RateLimiter`].
+async fn rate_limit(mut rx: Receiver<CommandEvent>, tx: EventSender, window: Duration) {
+    let mut limiter = RateLimiter::new(window);
+    loop {
+        let events = match limiter.deadline() {
+            None => rx.recv().await.map(|event| limiter.push(event)),
+            Some(deadline) => match tokio::time::timeout_at(deadline.into(), rx.recv()).await {
+                Ok(event) => event.map(|event| limiter.push(event)),
+                Err(_) => Some([limiter.window_ended(), None]),
+            },
+        };
+        // returning drops `rx`, which stops the reader once nobody is listening anymore
+        let Some(events) = events else {
+            if let Some(event) = limiter.flush() {
+                let _ = tx.send_async(event).await;
+            }
+            return;
+        };
+        for event in events.into_iter().flatten() {
+            if tx.send_async(event).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Forwards the events of `rx` to `tx` through a [`RateLimiter`], blocking the current thread.
+fn rate_limit_blocking(rx: mpsc::Receiver<CommandEvent>, tx: EventSender, window: Duration) {
+    let mut limiter = RateLimiter::new(window);
+    loop {
+        let received = match limiter.deadline() {
+            None => rx.recv().map_err(|_| mpsc::RecvTimeoutError::Disconnected),
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        };
+        let events = match received {
+            Ok(event) => limiter.push(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => [limiter.window_ended(), None],
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                if let Some(event) = limiter.flush() {
+                    let _ = tx.send(event);
+                }
+                return;
+            }
+        };
+        for event in events.into_iter().flatten() {
+            if tx.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_output() {
+        let cmd = Command::new("cat").args(["test/test.txt"]);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Terminated(payload) => {
+                        assert_eq!(payload.code, Some(0));
+                    }
+                    CommandEvent::Stdout(line) => {
+                        assert_eq!(
+                            String::from_utf8(line.into()).unwrap(),
+                            "This is a test doc!"
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_raw_output() {
+        let cmd = Command::new("cat").args(["test/test.txt"]);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Terminated(payload) => {
+                        assert_eq!(payload.code, Some(0));
+                    }
+                    CommandEvent::Stdout(line) => {
+                        assert_eq!(
+                            String::from_utf8(line.into()).unwrap(),
+                            "This is a test doc!"
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    // test the failure case
+    fn test_cmd_spawn_fail() {
+        let cmd = Command::new("cat").args(["test/"]);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Terminated(payload) => {
+                        assert_eq!(payload.code, Some(1));
+                    }
+                    CommandEvent::Stderr(line) => {
+                        assert_eq!(
+                            String::from_utf8(line.into()).unwrap(),
+                            "cat: test/: Is a directory\n"
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    // test the failure case (raw encoding)
+    fn test_cmd_spawn_raw_fail() {
+        let cmd = Command::new("cat").args(["test/"]);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Terminated(payload) => {
+                        assert_eq!(payload.code, Some(1));
+                    }
+                    CommandEvent::Stderr(line) => {
+                        assert_eq!(
+                            String::from_utf8(line.into()).unwrap(),
+                            "cat: test/: Is a directory\n"
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_output() {
+        let cmd = Command::new("cat").args(["test/test.txt"]);
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "This is a test doc!"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_output_fail() {
+        let cmd = Command::new("cat").args(["test/"]);
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "cat: test/: Is a directory\n"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_dual_capture_output() {
+        let cmd = Command::new("sh").args(["-c", "echo out; sleep 0.1; echo err >&2"]);
+        let output = tauri::async_runtime::block_on(cmd.dual_capture().output()).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "out\n");
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), "err\n");
+        assert_eq!(String::from_utf8(output.merged).unwrap(), "out\nerr\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_with_argv() {
+        let cmd = Command::with_argv(
+            "sh".into(),
+            vec!["my-shim".into(), "-c".into(), "echo $0".into()],
+        );
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "my-shim\n");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_line_prefix() {
+        let cmd = Command::new("cat")
+            .args(["test/test.txt"])
+            .line_prefix(b"[cat] ".to_vec());
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    assert_eq!(
+                        String::from_utf8(line.into()).unwrap(),
+                        "[cat] This is a test doc!"
+                    );
+                }
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_recv_timeout() {
+        let cmd = Command::new("sh").args(["-c", "sleep 0.5; echo done"]);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            assert!(recv_timeout(&mut rx, Duration::from_millis(50))
+                .await
+                .unwrap()
+                .is_none());
+
+            let event = recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap();
+            assert!(matches!(event, Some(CommandEvent::Stdout(_))));
+
+            let event = recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap();
+            assert!(matches!(event, Some(CommandEvent::Terminated(_))));
+
+            assert!(matches!(
+                recv_timeout(&mut rx, Duration::from_secs(5)).await,
+                Err(crate::Error::EventStreamClosed)
+            ));
+        });
+    }
+
+    #[test]
+    fn test_restart_backoff_delay() {
+        let backoff = RestartBackoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            factor: 2.0,
+        };
+        assert_eq!(backoff.delay(1), Duration::from_millis(100));
+        assert_eq!(backoff.delay(2), Duration::from_millis(200));
+        assert_eq!(backoff.delay(4), Duration::from_millis(800));
+        assert_eq!(backoff.delay(5), Duration::from_secs(1));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_supervised_restarts() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo run; exit 1"])
+            .restart_backoff(Duration::from_millis(10), Duration::from_secs(1), 2.0);
+        let (mut rx, supervisor) = cmd.spawn_supervised().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            let mut runs = 0;
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    assert_eq!(payload.code, Some(1));
+                    runs += 1;
+                    if runs == 3 {
+                        break;
+                    }
+                }
+            }
+            supervisor.stop().unwrap();
+            assert_eq!(runs, 3);
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_oom_score_adj() {
+        let cmd = Command::new("sleep").args(["5"]);
+        let (_rx, child) = cmd.spawn().unwrap();
+
+        child.set_oom_score_adj(500).unwrap();
+        assert_eq!(child.oom_score_adj().unwrap(), 500);
+        assert!(child.set_oom_score_adj(1001).is_err());
+
+        child.kill().unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_status_blocking() {
+        let status = Command::new("cat")
+            .args(["test/test.txt"])
+            .status_blocking()
+            .unwrap();
+        assert!(status.success());
+
+        let status = Command::new("sh")
+            .args(["-c", "kill -9 $$"])
+            .status_blocking()
+            .unwrap();
+        assert_eq!(status.code(), None);
+        assert_eq!(status.signal(), Some(9));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_spawn_inherit_tty() {
+        let cmd = Command::new("true").inherit_tty();
+        let (mut rx, mut child) = cmd.spawn().unwrap();
+        assert!(child.write(b"input").is_err());
+
+        tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
+            while let Some(event) = rx.recv().await {
+                events.push(event);
+            }
+            assert!(matches!(
+                events.as_slice(),
+                [CommandEvent::Terminated(TerminatedPayload {
+                    code: Some(0),
+                    ..
+                })]
+            ));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_env_fn() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo $TAURI_A $TAURI_B $TAURI_PROGRAM"])
+            .env("TAURI_A", "a")
+            .env("TAURI_B", "b")
+            .env_fn(Arc::new(|program, env| {
+                assert_eq!(env.get("TAURI_A").map(String::as_str), Some("a"));
+                env.remove("TAURI_B");
+                env.insert(
+                    "TAURI_PROGRAM".into(),
+                    Path::new(program)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into(),
+                );
+            }));
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "a sh\n");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_wait_timeout() {
+        let (_rx, child) = Command::new("sleep").args(["5"]).spawn().unwrap();
+        assert!(matches!(
+            child.wait_timeout(Duration::from_millis(50)).unwrap(),
+            WaitOutcome::TimedOut
+        ));
+        child.kill().unwrap();
+
+        let (_rx, child) = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        match child.wait_timeout(Duration::from_secs(5)).unwrap() {
+            WaitOutcome::Exited(status) => assert_eq!(status.code(), Some(3)),
+            WaitOutcome::TimedOut => panic!("process did not exit"),
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_to() {
+        let mut stdout = Vec::new();
+        let cmd = Command::new("sh").args(["-c", "echo out; echo err >&2"]);
+        let status = tauri::async_runtime::block_on(cmd.output_to(&mut stdout, 1024)).unwrap();
+        assert!(status.success());
+        assert_eq!(String::from_utf8(stdout).unwrap(), "out\n");
+
+        let cmd = Command::new("sh").args(["-c", "echo out; echo long error >&2; exit 2"]);
+        match tauri::async_runtime::block_on(cmd.output_to(std::io::sink(), 6)) {
+            Err(crate::Error::NonZeroExit { code, stderr }) => {
+                assert_eq!(code, Some(2));
+                assert_eq!(String::from_utf8(stderr).unwrap(), "error\n");
+            }
+            other => panic!("unexpected result {other:?}"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_which() {
+        let sh = which("sh").unwrap();
+        assert!(sh.is_absolute());
+        assert_eq!(sh.file_name().unwrap(), "sh");
+        assert_eq!(which(sh.to_str().unwrap()), Some(sh));
+        assert_eq!(which("tauri-plugin-shell-missing-program"), None);
+        // not executable
+        assert_eq!(which("test/test.txt"), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_measure_first_output() {
+        let cmd = Command::new("sh")
+            .args(["-c", "sleep 0.1; echo out; echo err >&2"])
+            .measure_first_output();
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            let mut latencies = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::FirstOutput { latency } = event {
+                    latencies.push(latency);
+                }
+            }
+            assert_eq!(latencies.len(), 1);
+            assert!(latencies[0] >= Duration::from_millis(100));
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_umask() {
+        let cmd = Command::new("sh").args(["-c", "umask"]).umask(0o027);
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "0027\n");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_duplex() {
+        let (mut stdin, mut rx, child) = Command::new("cat").spawn_duplex().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            stdin.write_all(b"ping\n").unwrap();
+            let event = recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap();
+            assert!(matches!(event, Some(CommandEvent::Stdout(line)) if line == b"ping\n"[..]));
+
+            // cat only exits once every stdin handle is closed
+            drop(stdin);
+            drop(child);
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    assert_eq!(payload.code, Some(0));
+                }
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_status_success_with() {
+        let status = Command::new("sh")
+            .args(["-c", "kill -15 $$"])
+            .status_blocking()
+            .unwrap();
+        assert!(!status.success());
+        assert!(status.success_with(&[15]));
+        assert!(!status.success_with(&[9]));
+
+        let status = Command::new("sh")
+            .args(["-c", "exit 1"])
+            .status_blocking()
+            .unwrap();
+        assert!(!status.success_with(&[15]));
+    }
+
+    /// A reader that fails with the given errors before returning its data.
+    struct FlakyReader {
+        errors: Vec<std::io::ErrorKind>,
+        data: std::io::Cursor<Vec<u8>>,
+    }
+
+    impl AsyncRead for FlakyReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            match self.errors.pop() {
+                Some(kind) => std::task::Poll::Ready(Err(kind.into())),
+                None => std::pin::Pin::new(&mut self.data).poll_read(cx, buf),
+            }
+        }
+    }
+
+    fn read_flaky(errors: Vec<std::io::ErrorKind>, raw_out: bool) -> Vec<CommandEvent> {
+        let reader = BufReader::new(FlakyReader {
+            errors,
+            data: std::io::Cursor::new(b"line\n".to_vec()),
+        });
+        let options = ReaderOptions {
+            raw_out,
+            ..Default::default()
+        };
+        let (tx, mut rx) = channel(10);
+        let tx = EventSender::Async(tx);
+        tauri::async_runtime::block_on(read_pipe(reader, tx, CommandEvent::Stdout, &options));
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    #[test]
+    fn test_reader_retries_interrupted() {
+        for raw_out in [true, false] {
+            let events = read_flaky(vec![std::io::ErrorKind::Interrupted; 3], raw_out);
+            assert!(
+                matches!(events.as_slice(), [CommandEvent::Stdout(line)] if line == &b"line\n"[..])
+            );
+        }
+    }
+
+    #[test]
+    fn test_reader_stops_on_error() {
+        for raw_out in [true, false] {
+            let events = read_flaky(vec![std::io::ErrorKind::BrokenPipe], raw_out);
+            assert!(matches!(events.as_slice(), [CommandEvent::Error(_)]));
+        }
+    }
+
+    /// A stream yielding the given chunks.
+    struct ChunkStream(std::collections::VecDeque<Vec<u8>>);
+
+    impl Stream for ChunkStream {
+        type Item = Vec<u8>;
+
+        fn poll_next(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Self::Item>> {
+            std::task::Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_feed_stdin() {
+        let (mut rx, mut child) = Command::new("cat").spawn().unwrap();
+        child
+            .feed_stdin(ChunkStream(
+                [b"first ".to_vec(), b"second\n".to_vec()].into(),
+            ))
+            .unwrap();
+        assert!(child.write(b"more").is_err());
+
+        tauri::async_runtime::block_on(async move {
+            let mut stdout = Vec::new();
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => stdout.extend(line),
+                    CommandEvent::Terminated(payload) => assert_eq!(payload.code, Some(0)),
+                    _ => {}
+                }
+            }
+            assert_eq!(String::from_utf8(stdout).unwrap(), "first second\n");
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_shared_child() {
+        let (_rx, child) = Command::new("sh").args(["-c", "exit 4"]).spawn().unwrap();
+        let shared = child.shared();
+        assert_eq!(shared.id(), child.pid());
+        assert_eq!(shared.wait().unwrap().code(), Some(4));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_drain_on_kill() {
+        for drain in [true, false] {
+            // the grandchild keeps writing to the pipe after the shell is killed
+            let cmd = Command::new("sh")
+                .args(["-c", "(sleep 0.2; echo late) & echo early; wait"])
+                .drain_on_kill(drain);
+            let (mut rx, child) = cmd.spawn().unwrap();
+
+            tauri::async_runtime::block_on(async move {
+                let event = recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap();
+                assert!(
+                    matches!(event, Some(CommandEvent::Stdout(line)) if line == b"early\n"[..])
+                );
+                child.kill().unwrap();
+
+                let mut late = false;
+                while let Some(event) = rx.recv().await {
+                    if let CommandEvent::Stdout(line) = event {
+                        late |= line == b"late\n"[..];
+                    }
+                }
+                assert_eq!(late, drain);
+            });
+        }
+    }
+
+    #[cfg(all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ))]
+    #[test]
+    fn test_cmd_seccomp() {
+        let path = std::env::temp_dir().join(format!("tauri-shell-seccomp-{}", std::process::id()));
+        let script = format!("echo data > {}", path.display());
+        let cmd = Command::new("sh")
+            .args(["-c", &script])
+            .seccomp(SeccompFilter::read_only_fs());
+        let status = cmd.status_blocking().unwrap();
+        assert!(!status.success());
+        assert!(!path.exists());
+
+        let cmd = Command::new("cat")
+            .args(["test/test.txt"])
+            .seccomp(SeccompFilter::read_only_fs().merge(SeccompFilter::no_network()));
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "This is a test doc!"
+        );
+
+        let cmd = Command::new("mkdir").args([&path]).seccomp(
+            SeccompFilter::new()
+                .deny(libc::SYS_mkdirat)
+                .deny(libc::SYS_mkdir),
+        );
+        assert!(!cmd.status_blocking().unwrap().success());
+        assert!(!path.exists());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_child_command() {
+        let cmd = Command::new("cat")
+            .args(["test/test.txt"])
+            .env("TAURI_A", "a");
+        let (mut rx, child) = cmd.clone().spawn().unwrap();
+        assert_eq!(child.command().args, cmd.args);
+        assert_eq!(child.command().envs, cmd.envs);
+
+        // the retained command can be spawned again
+        let output = tauri::async_runtime::block_on(async move {
+            while rx.recv().await.is_some() {}
+            child.command().clone().output().await.unwrap()
+        });
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "This is a test doc!"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_max_events_per_sec() {
+        let cmd = Command::new("sh")
+            .args(["-c", "for i in 1 2 3 4 5; do echo $i; done"])
+            .max_events_per_sec(2);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            let mut events = 0;
+            let mut lines = Vec::new();
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => {
+                        events += 1;
+                        lines.push(line);
+                    }
+                    CommandEvent::StdoutBatch(batch) => {
+                        events += 1;
+                        lines.extend(batch);
+                    }
+                    _ => {}
+                }
+            }
+            assert!(events < 5);
+            assert_eq!(lines.concat(), b"1\n2\n3\n4\n5\n");
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_capacity() {
+        let cmd = Command::new("cat")
+            .args(["test/test.txt"])
+            .output_capacity(4096, 1024);
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+        assert!(output.stdout.capacity() >= 4096);
+        assert!(output.stderr.capacity() >= 1024);
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "This is a test doc!"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_output_cancelled() {
+        let pid_file =
+            std::env::temp_dir().join(format!("tauri-shell-cancel-{}", std::process::id()));
+        let script = format!("echo $$ > {}; exec sleep 30", pid_file.display());
+        let cmd = Command::new("sh").args(["-c", &script]);
+
+        tauri::async_runtime::block_on(async {
+            assert!(
+                tokio::time::timeout(Duration::from_millis(500), cmd.output())
+                    .await
+                    .is_err()
+            );
+        });
+
+        let pid: i32 = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        // SAFETY: signal 0 only checks if the process exists
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(Instant::now() < deadline, "process {pid} is still running");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_restart_in() {
+        let (_rx, mut child) = Command::new("sleep").args(["5"]).spawn().unwrap();
+        assert!(matches!(child.restart(), Err(crate::Error::ProcessRunning)));
+        child.kill().unwrap();
+
+        let (mut rx, mut child) = Command::new("pwd").spawn().unwrap();
+        tauri::async_runtime::block_on(async move {
+            while rx.recv().await.is_some() {}
+
+            let dir = std::fs::canonicalize("test").unwrap();
+            let mut rx = child.restart_in(dir.clone()).unwrap();
+            let mut stdout = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    stdout.extend(line);
+                }
+            }
+            assert_eq!(
+                String::from_utf8(stdout).unwrap(),
+                format!("{}\n", dir.display())
+            );
+            assert_eq!(child.command().current_dir, Some(dir));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_sync_channel() {
+        let cmd = Command::new("cat").args(["test/test.txt"]);
+        let (rx, _) = cmd.spawn_sync_channel().unwrap();
+
+        let events: Vec<_> = rx.iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [CommandEvent::Stdout(line), CommandEvent::Terminated(payload)]
+                if line == &b"This is a test doc!"[..] && payload.code == Some(0)
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_sync_channel_chatty() {
+        const LINES: usize = 100_000;
+        let cmd = Command::new("sh")
+            .args(["-c", &format!("seq {LINES}; echo done >&2")])
+            .event_buffer(1);
+        // the readers run on their own threads, drained from a plain thread with a single slot
+        for cmd in [
+            cmd.clone(),
+            cmd.clone().stream_priority(Priority::Stderr),
+            cmd.max_events_per_sec(20),
+        ] {
+            let (rx, _child) = cmd.spawn_sync_channel().unwrap();
+            let receiver = std::thread::spawn(move || {
+                let mut lines = 0;
+                let mut code = None;
+                for event in rx {
+                    match event {
+                        CommandEvent::Stdout(_) => lines += 1,
+                        CommandEvent::StdoutBatch(batch) => lines += batch.len(),
+                        CommandEvent::Terminated(payload) => code = payload.code,
+                        _ => {}
+                    }
+                }
+                (lines, code)
+            });
+            assert_eq!(receiver.join().unwrap(), (LINES, Some(0)));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_shutdown() {
+        let (rx, child) = Command::new("sleep")
+            .args(["30"])
+            .spawn_sync_channel()
+            .unwrap();
+
+        let status = child.shutdown().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        // every thread holding a sender has exited
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(CommandEvent::Terminated(payload)) if payload.signal == Some(libc::SIGKILL)
+        ));
+        assert!(matches!(
+            rx.try_recv(),
+            Err(std::sync::mpsc::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_threads_exit_after_receiver_dropped() {
+        let (rx, mut child) = Command::new("seq").args(["1", "10000"]).spawn().unwrap();
+        drop(rx);
+
+        let waiter = child.waiter.take().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !waiter.is_finished() {
+            assert!(Instant::now() < deadline, "the wait thread is stuck");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // the wait thread joins the readers, so every sender is gone
+        assert!(child.events.upgrade().is_none());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_stdout_lines() {
+        let cmd = Command::new("printf").args(["one\\ntwo\\r\\n\\xff\\n\\nlast"]);
+        let lines = tauri::async_runtime::block_on(cmd.clone().stdout_lines()).unwrap();
+        assert_eq!(lines, ["one", "two", "\u{FFFD}", "", "last"]);
+
+        let lines =
+            tauri::async_runtime::block_on(cmd.set_raw_out(true).stdout_lines_bytes()).unwrap();
+        assert_eq!(lines[2], b"\xff");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_tee_stdout() {
+        let path = std::env::temp_dir().join(format!("tauri-shell-tee-{}", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        let (mut rx, _) = Command::new("cat")
+            .args(["test/test.txt"])
+            .line_prefix(b"> ".to_vec())
+            .tee_stdout(file)
+            .spawn()
+            .unwrap();
+
+        let mut stdout = Vec::new();
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    stdout.push(line);
+                }
+            }
+        });
+        assert_eq!(stdout, [&b"> This is a test doc!"[..]]);
+
+        let tee = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(tee, "This is a test doc!");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_stdin_str() {
+        let input = "línea\n".repeat(100_000);
+        let lines = tauri::async_runtime::block_on(
+            Command::new("wc")
+                .args(["-l"])
+                .stdin_str(input)
+                .stdout_lines(),
+        )
+        .unwrap();
+        assert_eq!(lines[0].trim(), "100000");
+
+        let hex = |command: Command| {
+            let lines = tauri::async_runtime::block_on(
+                command
+                    .args(["-An", "-tx1"])
+                    .stdin_str("日本".into())
+                    .stdout_lines(),
+            )
+            .unwrap();
+            lines
+                .concat()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        assert_eq!(hex(Command::new("od")), "e6 97 a5 e6 9c ac");
+        assert_eq!(
+            hex(Command::new("od").encoding(encoding_rs::SHIFT_JIS)),
+            "93 fa 96 7b"
+        );
+
+        // a process that doesn't read its stdin is not an error
+        let (mut rx, _) = Command::new("true")
+            .stdin_str("ignored".repeat(100_000))
+            .spawn()
+            .unwrap();
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                assert!(!matches!(event, CommandEvent::Error(_)), "{event:?}");
+            }
+        });
+    }
+
+    #[cfg(all(not(windows), feature = "diagnostics"))]
+    #[test]
+    fn test_active_reader_threads() {
+        let (_rx, child) = Command::new("sleep").args(["30"]).spawn().unwrap();
+        // the stdout and stderr readers and the wait thread, other tests may run concurrently
+        assert!(active_reader_threads() >= 3);
+        child.shutdown().unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_checked() {
+        let exit_code_map = HashMap::from([(3, "three".to_string())]);
+        let run = |code: &str| {
+            tauri::async_runtime::block_on(
+                Command::new("sh")
+                    .args(["-c", &format!("echo failed >&2; exit {code}")])
+                    .exit_code_map(exit_code_map.clone())
+                    .output_checked(),
+            )
+        };
+
+        assert!(run("0").is_ok());
+        assert!(matches!(
+            run("3"),
+            Err(crate::Error::KnownExit { code: 3, meaning }) if meaning == "three"
+        ));
+        assert!(matches!(
+            run("4"),
+            Err(crate::Error::NonZeroExit { code: Some(4), stderr }) if stderr.starts_with(b"failed")
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_suspend_resume() {
+        let (_rx, child) = Command::new("sleep").args(["30"]).spawn().unwrap();
+        let state = || {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", child.pid())).unwrap();
+            // the state follows the parenthesized command name
+            stat.rsplit_once(") ").unwrap().1.chars().next().unwrap()
+        };
+        let wait_for = |expected: bool| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while (state() == 'T') != expected {
+                assert!(Instant::now() < deadline, "state is {}", state());
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        child.suspend().unwrap();
+        wait_for(true);
+        child.resume().unwrap();
+        wait_for(false);
+        child.shutdown().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_stdbuf_line() {
+        let (mut rx, child) = Command::new("sh")
+            .args(["-c", "printf 'ready\\n'; exec sleep 30"])
+            .stdbuf_line()
+            .spawn()
+            .unwrap();
+        let command: StdCommand = child.command().clone().into();
+        if which("stdbuf").is_some() {
+            assert!(command.get_program().to_string_lossy().ends_with("stdbuf"));
+            assert_eq!(
+                command.get_args().take(3).collect::<Vec<_>>(),
+                ["-oL", "-eL", "sh"]
+            );
+        }
+
+        tauri::async_runtime::block_on(async move {
+            let event = recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap();
+            assert!(matches!(event, Some(CommandEvent::Stdout(line)) if line == b"ready\n"[..]));
+        });
+        child.shutdown().unwrap();
+
+        // the fallback and the hook see the wrapped program, not stdbuf
+        let programs = Arc::new(Mutex::new(Vec::new()));
+        let programs_ = programs.clone();
+        let command = Command::new("tauri-shell-missing-program")
+            .or_program("sh")
+            .args(["-c", "echo \"$HOOKED\""])
+            .stdbuf_line()
+            .env_fn(Arc::new(move |program, env| {
+                programs_.lock().unwrap().push(program.to_string());
+                env.insert("HOOKED".into(), "yes".into());
+            }));
+        assert!(command.diagnostics().resolved_program.is_none());
+        let lines = tauri::async_runtime::block_on(command.stdout_lines()).unwrap();
+        assert_eq!(lines, ["yes"]);
+        let programs = programs.lock().unwrap();
+        assert!(
+            programs.iter().all(|program| !program.ends_with("stdbuf")),
+            "{programs:?}"
+        );
+        assert!(
+            programs.iter().any(|program| program.ends_with("/sh")),
+            "{programs:?}"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_diagnostics() {
+        let diagnostics = Command::new("sh")
+            .args(["-c", "true"])
+            .env("TAURI_SHELL_DIAGNOSTICS", "1")
+            .current_dir("/")
+            .diagnostics();
+
+        assert!(diagnostics
+            .resolved_program
+            .is_some_and(|path| path.ends_with("sh")));
+        assert_eq!(diagnostics.argv, ["sh", "-c", "true"]);
+        assert_eq!(
+            diagnostics.env_set,
+            BTreeMap::from([("TAURI_SHELL_DIAGNOSTICS".into(), "1".into())])
+        );
+        assert!(diagnostics.env_removed.is_empty());
+        assert_eq!(diagnostics.current_dir.as_deref(), Some(Path::new("/")));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_status_cancellable() {
+        let (status, child) = Command::new("sleep")
+            .args(["30"])
+            .status_cancellable()
+            .unwrap();
+        child.kill().unwrap();
+        let status = tauri::async_runtime::block_on(status).unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_keep_last_bytes() {
+        let cmd = Command::new("seq").args(["1", "1000"]).set_raw_out(true);
+        let full = tauri::async_runtime::block_on(cmd.clone().output()).unwrap();
+        let tail = tauri::async_runtime::block_on(cmd.keep_last_bytes(10).output()).unwrap();
+
+        assert_eq!(tail.stdout_total, full.stdout.len());
+        assert_eq!(tail.stdout, &full.stdout[full.stdout.len() - 10..]);
+        assert!(tail.stderr.is_empty());
+        assert_eq!(tail.stderr_total, 0);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_or_program() {
+        let cmd = Command::new("tauri-shell-missing-1")
+            .or_program("tauri-shell-missing-2")
+            .or_program("echo")
+            .args(["fallback"]);
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["fallback"]);
+
+        let err = Command::new("tauri-shell-missing-1")
+            .or_program("tauri-shell-missing-2")
+            .spawn()
+            .unwrap_err();
+        assert!(matches!(&err, crate::Error::ProgramNotFound(attempted) if attempted.len() == 2));
+        assert_eq!(
+            err.to_string(),
+            "program not found, tried tauri-shell-missing-1, tauri-shell-missing-2"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_normalize_newlines() {
+        let cmd = Command::new("printf").args(["a\\r\\nb\\rc\\n"]);
+        let lines = |cmd: Command| {
+            let (mut rx, _) = cmd.spawn().unwrap();
+            let mut lines = Vec::new();
+            tauri::async_runtime::block_on(async {
+                while let Some(event) = rx.recv().await {
+                    if let CommandEvent::Stdout(line) = event {
+                        lines.push(line);
+                    }
+                }
+            });
+            lines
+        };
+
+        assert_eq!(lines(cmd.clone()), [&b"a\r\n"[..], b"b\rc\n"]);
+        assert_eq!(lines(cmd.normalize_newlines()), [&b"a\n"[..], b"b\nc\n"]);
+
+        // the `\r` is read before the `\n` is written
+        let cmd = Command::new("sh").args(["-c", "printf 'a\\r'; sleep 0.2; printf '\\nb\\n'"]);
+        assert_eq!(lines(cmd.clone()), [&b"a\r"[..], b"\n", b"b\n"]);
+        assert_eq!(lines(cmd.normalize_newlines()), [&b"a\n"[..], b"b\n"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_subscribe_exit() {
+        let (_rx, child) = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let first = child.subscribe_exit();
+        let second = child.subscribe_exit();
+
+        tauri::async_runtime::block_on(async move {
+            assert_eq!(first.await.unwrap().code(), Some(3));
+            assert_eq!(second.await.unwrap().code(), Some(3));
+            // subscribing after the process exited resolves immediately
+            assert_eq!(child.subscribe_exit().await.unwrap().code(), Some(3));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_stream_priority() {
+        let (mut rx, child) = Command::new("sh")
+            .args(["-c", "seq 1 1000; echo error >&2"])
+            .stream_priority(Priority::Stderr)
+            .spawn()
+            .unwrap();
+        // all of the output is pending in the pipes and the channel is full
+        assert!(matches!(
+            child.wait_timeout(Duration::from_secs(5)).unwrap(),
+            WaitOutcome::Exited(_)
+        ));
+        std::thread::sleep(Duration::from_millis(100));
+
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
+            while let Some(event) = rx.recv().await {
+                events.push(event);
+            }
+            events
+        });
+        let position = events
+            .iter()
+            .position(|event| matches!(event, CommandEvent::Stderr(_)))
+            .unwrap();
+        // only the stdout events already dispatched can come before it
+        assert!(position < 3, "stderr delivered at {position}");
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, CommandEvent::Stdout(_)))
+                .count(),
+            1000
+        );
+        assert!(matches!(events.last(), Some(CommandEvent::Terminated(_))));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_until_match() {
+        tauri::async_runtime::block_on(async {
+            let (groups, child) = Command::new("sh")
+                .args([
+                    "-c",
+                    "echo starting; echo 'listening on port 8080'; exec sleep 30",
+                ])
+                .spawn_until_match(r"listening on (port|socket) (\d+)(!)?")
+                .await
+                .unwrap();
+            assert_eq!(groups, ["port", "8080", ""]);
+            child.shutdown().unwrap();
+
+            let result = Command::new("echo")
+                .args(["nothing"])
+                .spawn_until_match("listening")
+                .await;
+            assert!(matches!(result, Err(crate::Error::NoMatch(_))));
+
+            let result = Command::new("echo").spawn_until_match("(").await;
+            assert!(matches!(result, Err(crate::Error::Regex(_))));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_until_match_timeout() {
+        tauri::async_runtime::block_on(async {
+            let pid_file = std::env::temp_dir().join(format!(
+                "tauri-plugin-shell-until-match-{}",
+                std::process::id()
+            ));
+            let start = Instant::now();
+            let result = Command::new("sh")
+                .args(["-c", "echo $$ > \"$0\"; echo starting; exec sleep 30"])
+                .args([&pid_file])
+                .spawn_until_match_timeout("listening", Duration::from_millis(500))
+                .await;
+            assert!(matches!(result, Err(crate::Error::MatchTimedOut(_))));
+            assert!(start.elapsed() < Duration::from_secs(10));
+
+            let pid: i32 = std::fs::read_to_string(&pid_file)
+                .unwrap()
+                .trim()
+                .parse()
+                .unwrap();
+            let _ = std::fs::remove_file(&pid_file);
+            // the waiter thread reaps the killed process
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while unsafe { libc::kill(pid, 0) } == 0 {
+                assert!(Instant::now() < deadline, "the process wasn't killed");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_arg_chaining() {
+        let cmd = Command::new("printf")
+            .arg("%s,")
+            .args(["a", "b"])
+            .arg(String::from("c"));
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["a,b,c,"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_delimiter() {
+        let (mut rx, _) = Command::new("printf")
+            .args(["a\\nb\\0c\\0d"])
+            .delimiter(0)
+            .spawn()
+            .unwrap();
+        let mut chunks = Vec::new();
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(chunk) = event {
+                    chunks.push(chunk);
+                }
+            }
+        });
+        assert_eq!(chunks, [&b"a\nb\0"[..], b"c\0", b"d"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_env_chaining() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo $A,$B,$C"])
+            .env("A", "1")
+            .env("B", "2")
+            .envs([("B", "3"), ("C", "4")]);
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["1,3,4"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_session() {
+        tauri::async_runtime::block_on(async {
+            let mut session = Command::new("cat").start().unwrap();
+            session.write(b"ping\n").unwrap();
+            assert!(
+                matches!(session.recv().await, Some(CommandEvent::Stdout(line)) if line == b"ping\n"[..])
+            );
+
+            let exited = session.exited();
+            drop(session);
+            assert_eq!(exited.await.unwrap().signal(), Some(libc::SIGKILL));
+
+            let session = Command::new("sh").args(["-c", "exit 3"]).start().unwrap();
+            let (_events, child) = session.detach();
+            assert_eq!(child.subscribe_exit().await.unwrap().code(), Some(3));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_env_remove() {
+        std::env::set_var("TAURI_SHELL_INHERITED", "1");
+        let cmd = Command::new("env")
+            .envs([("TAURI_SHELL_ADDED", "1"), ("TAURI_SHELL_KEPT", "1")])
+            .env_remove("TAURI_SHELL_INHERITED")
+            .env_remove("TAURI_SHELL_ADDED");
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert!(lines.iter().any(|line| line == "TAURI_SHELL_KEPT=1"));
+        assert!(!lines
+            .iter()
+            .any(|line| line.starts_with("TAURI_SHELL_INHERITED=")));
+        assert!(!lines
+            .iter()
+            .any(|line| line.starts_with("TAURI_SHELL_ADDED=")));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_encoding() {
+        // "café" and "日本" in windows-1252 and Shift_JIS
+        let cmd = Command::new("sh")
+            .args(["-c", r"printf 'caf\351\n'"])
+            .encoding(encoding_rs::WINDOWS_1252);
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["café"]);
+
+        // the first character is split across two chunks
+        let cmd = Command::new("sh")
+            .args(["-c", r"printf '\223'; sleep 0.1; printf '\372\226\173'"])
+            .encoding(encoding_rs::SHIFT_JIS)
+            .set_raw_out(true);
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let stdout = tauri::async_runtime::block_on(async move {
+            let mut stdout = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(chunk) = event {
+                    stdout.extend(chunk);
+                }
+            }
+            stdout
+        });
+        assert_eq!(String::from_utf8(stdout).unwrap(), "日本");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_raw_chunk_size() {
+        let cmd = Command::new("printf")
+            .args([r"a\nb\0c\rd"])
+            .set_raw_out(true)
+            .raw_chunk_size(3);
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let chunks = tauri::async_runtime::block_on(async move {
+            let mut chunks = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(chunk) = event {
+                    chunks.push(chunk);
+                }
+            }
+            chunks
+        });
+        assert!(chunks
+            .iter()
+            .all(|chunk| !chunk.is_empty() && chunk.len() <= 3));
+        assert_eq!(chunks.concat(), b"a\nb\0c\rd");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_with_timeout() {
+        let cmd = Command::new("sh").args(["-c", "echo started; exec sleep 10"]);
+        let start = Instant::now();
+        let result =
+            tauri::async_runtime::block_on(cmd.output_with_timeout(Duration::from_millis(500)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let Err(crate::Error::TimedOut(output)) = result else {
+            panic!("expected a timeout, got {result:?}");
+        };
+        assert!(!output.status.success());
+        assert!(output.stdout.starts_with(b"started\n"));
+
+        let cmd = Command::new("true");
+        let output =
+            tauri::async_runtime::block_on(cmd.output_with_timeout(Duration::from_secs(5)))
+                .unwrap();
+        assert!(output.status.success());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_status_with_timeout() {
+        let cmd = Command::new("sleep").args(["10"]);
+        let start = Instant::now();
+        let status =
+            tauri::async_runtime::block_on(cmd.status_with_timeout(Duration::from_millis(100)))
+                .unwrap();
+        assert!(status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let cmd = Command::new("sh").args(["-c", "exit 3"]);
+        let status =
+            tauri::async_runtime::block_on(cmd.status_with_timeout(Duration::from_secs(5)))
+                .unwrap();
+        assert_eq!(status.unwrap().code(), Some(3));
+
+        // dropping the future, e.g. in a `select!`, kills the process
+        let pid_file =
+            std::env::temp_dir().join(format!("tauri-shell-status-timeout-{}", std::process::id()));
+        let _ = std::fs::remove_file(&pid_file);
+        let cmd = Command::new("sh").args([
+            "-c".to_string(),
+            format!("echo $$ > '{}'; exec sleep 30", pid_file.display()),
+        ]);
+        let dropped = tauri::async_runtime::block_on(async move {
+            tokio::time::timeout(
+                Duration::from_millis(500),
+                cmd.status_with_timeout(Duration::from_secs(30)),
+            )
+            .await
+        });
+        assert!(dropped.is_err());
+        let pid: libc::pid_t = std::fs::read_to_string(&pid_file)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let _ = std::fs::remove_file(&pid_file);
+        let start = Instant::now();
+        // SAFETY: kill with the null signal only checks whether the process exists
+        while unsafe { libc::kill(pid, 0) } == 0 {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "the process was not killed"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_terminate() {
+        // exits on SIGTERM
+        let cmd = Command::new("sleep").args(["10"]);
+        let (rx, child) = cmd.spawn().unwrap();
+        let start = Instant::now();
+        child.terminate(Duration::from_secs(5)).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let status = tauri::async_runtime::block_on(collect_status(rx));
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+
+        // ignores SIGTERM, so it's killed after the grace period
+        let cmd = Command::new("sh").args([
+            "-c",
+            "trap '' TERM; echo ready; while :; do sleep 0.1; done",
+        ]);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        tauri::async_runtime::block_on(rx.recv()).unwrap();
+        child.terminate(Duration::from_millis(200)).unwrap();
+        let status = tauri::async_runtime::block_on(collect_status(rx));
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_signal() {
+        let cmd = Command::new("sh").args([
+            "-c",
+            "trap 'echo reload' HUP; echo ready; while :; do sleep 0.1; done",
+        ]);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        tauri::async_runtime::block_on(async {
+            for _ in 0..2 {
+                // wait until the trap is installed or the previous signal is handled
+                let event = recv_timeout(&mut rx, Duration::from_secs(5)).await;
+                assert!(matches!(event, Ok(Some(CommandEvent::Stdout(_)))));
+                child.signal(libc::SIGHUP).unwrap();
+            }
+            let event = recv_timeout(&mut rx, Duration::from_secs(5)).await;
+            assert!(
+                matches!(event, Ok(Some(CommandEvent::Stdout(line))) if line == b"reload\n"[..])
+            );
+        });
+        child.kill().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_kill_on_drop() {
+        let cmd = Command::new("sleep").args(["10"]).kill_on_drop(true);
+        let (rx, child) = cmd.spawn().unwrap();
+        let pid = child.pid();
+        drop(child);
+        let status = tauri::async_runtime::block_on(async move {
+            tokio::time::timeout(Duration::from_secs(5), collect_status(rx)).await
+        })
+        .expect("the process was not killed");
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        // SAFETY: signal 0 only checks whether the process exists
+        assert_eq!(unsafe { libc::kill(pid as i32, 0) }, -1);
+
+        // disabled by default
+        let cmd = Command::new("sleep").args(["10"]);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        let pid = child.pid();
+        drop(child);
+        let event =
+            tauri::async_runtime::block_on(recv_timeout(&mut rx, Duration::from_millis(200)));
+        assert!(matches!(event, Ok(None)));
+        // SAFETY: see above
+        assert_eq!(unsafe { libc::kill(pid as i32, libc::SIGKILL) }, 0);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_wait() {
+        let cmd = Command::new("sh").args(["-c", "echo out; exit 4"]);
+        let (rx, child) = cmd.spawn().unwrap();
+        tauri::async_runtime::block_on(async move {
+            let status = child.wait().await.unwrap();
+            assert_eq!(status.code(), Some(4));
+            // the termination event is still emitted
+            assert_eq!(collect_status(rx).await.code(), Some(4));
+            // the status is kept once the process exited
+            assert_eq!(child.wait().await.unwrap().code(), Some(4));
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_try_wait() {
+        let cmd = Command::new("sleep").args(["1"]);
+        let (_rx, child) = cmd.spawn().unwrap();
+        assert!(child.try_wait().unwrap().is_none());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let status = loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                break status;
+            }
+            assert!(Instant::now() < deadline, "the process didn't exit");
+            std::thread::sleep(Duration::from_millis(50));
+        };
+        assert_eq!(status.code(), Some(0));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_close_stdin() {
+        let (mut rx, mut child) = Command::new("sort").spawn().unwrap();
+        child.write(b"b\na\n").unwrap();
+        child.close_stdin().unwrap();
+        assert!(child.write(b"c\n").is_err());
+        assert!(child.close_stdin().is_err());
+
+        tauri::async_runtime::block_on(async move {
+            let mut lines = Vec::new();
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+                    Ok(Some(CommandEvent::Stdout(line))) => lines.push(line),
+                    Ok(Some(CommandEvent::Terminated(payload))) => {
+                        assert_eq!(payload.code, Some(0));
+                        break;
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) | Err(_) => {
+                        panic!("the process didn't exit after its stdin was closed")
+                    }
+                }
+            }
+            assert_eq!(lines, [&b"a\n"[..], b"b\n"]);
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_write_line() {
+        let (mut rx, mut child) = Command::new("cat").spawn().unwrap();
+        tauri::async_runtime::block_on(async move {
+            for line in ["first", "second"] {
+                child.write_line(line).unwrap();
+                let event = recv_timeout(&mut rx, Duration::from_secs(5)).await;
+                assert!(
+                    matches!(event, Ok(Some(CommandEvent::Stdout(echoed))) if echoed == format!("{line}\n").as_bytes())
+                );
+            }
+            child.kill().unwrap();
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_terminated_duration() {
+        let (mut rx, _child) = Command::new("sleep").args(["0.2"]).spawn().unwrap();
+        tauri::async_runtime::block_on(async move {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    assert!((200..5000).contains(&payload.duration_ms));
+                    return;
+                }
+            }
+            panic!("no termination event");
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_running_pids() {
+        let (rx, child) = Command::new("sleep").args(["10"]).spawn().unwrap();
+        let pid = child.pid();
+        assert!(running_pids().contains(&pid));
+        child.kill().unwrap();
+        tauri::async_runtime::block_on(collect_status(rx));
+        assert!(!running_pids().contains(&pid));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_event_buffer() {
+        let cmd = Command::new("seq").args(["1", "10"]).event_buffer(16);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        // the readers and the wait thread finish without anything receiving the events
+        let waiter = child.waiter.as_ref().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !waiter.is_finished() {
+            assert!(Instant::now() < deadline, "the events were not buffered");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let mut lines = 0;
+        while let Ok(event) = rx.try_recv() {
+            if let CommandEvent::Stdout(_) = event {
+                lines += 1;
+            }
+        }
+        assert_eq!(lines, 10);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_detached() {
+        let pid = Command::new("sleep")
+            .args(["10"])
+            .detached()
+            .spawn()
+            .unwrap();
+        assert!(!running_pids().contains(&pid));
+        // SAFETY: getsid and kill only take the pid
+        unsafe {
+            assert_eq!(libc::getsid(pid as i32), pid as i32);
+            assert_eq!(libc::kill(pid as i32, libc::SIGKILL), 0);
+        }
+
+        let pid = Command::new("sleep")
+            .args(["10"])
+            .process_group(true)
+            .detached()
+            .spawn()
+            .unwrap();
+        // SAFETY: getsid, getpgid and kill only take the pid
+        unsafe {
+            assert_eq!(libc::getsid(pid as i32), pid as i32);
+            assert_eq!(libc::getpgid(pid as i32), pid as i32);
+            assert_eq!(libc::kill(pid as i32, libc::SIGKILL), 0);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cmd_uid_gid() {
+        // SAFETY: these calls can't fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        // only root can switch to another user, otherwise keep the current ids
+        let (uid, gid) = if uid == 0 { (65534, 65534) } else { (uid, gid) };
+        let cmd = Command::new("sh")
+            .args(["-c", "id -u; id -g"])
+            .uid(uid)
+            .gid(gid);
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, [uid.to_string(), gid.to_string()]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_process_group() {
+        let cmd = Command::new("sh")
+            .args(["-c", "sleep 100 & echo $!; wait"])
+            .process_group(true);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        let grandchild: i32 = tauri::async_runtime::block_on(async {
+            match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+                Ok(Some(CommandEvent::Stdout(line))) => String::from_utf8(line.into())
+                    .unwrap()
+                    .trim()
+                    .parse()
+                    .unwrap(),
+                event => panic!("unexpected event {event:?}"),
             }
+        });
+        child.kill().unwrap();
+        tauri::async_runtime::block_on(collect_status(rx));
+
+        // the orphaned grandchild might linger as a zombie until its new parent reaps it
+        let state = || {
+            std::fs::read_to_string(format!("/proc/{grandchild}/stat"))
+                .ok()
+                .and_then(|stat| stat.rsplit(") ").next()?.chars().next())
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !matches!(state(), None | Some('Z')) {
+            assert!(Instant::now() < deadline, "the grandchild is still running");
+            std::thread::sleep(Duration::from_millis(10));
         }
     }
-}
 
-fn read_line<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
-    mut reader: BufReader<PipeReader>,
-    tx: Sender<CommandEvent>,
-    wrapper: F,
-) {
-    loop {
-        let mut buf = Vec::new();
-        match tauri::utils::io::read_line(&mut reader, &mut buf) {
-            Ok(n) => {
-                if n == 0 {
-                    break;
-                }
-                let tx_ = tx.clone();
-                let _ = block_on_task(async move { tx_.send(wrapper(buf)).await });
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_kill_tree() {
+        // a grandchild spawning a great-grandchild
+        let cmd = Command::new("sh").args(["-c", "(sleep 100 & echo $!; wait) & wait"]);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        let descendant: i32 = tauri::async_runtime::block_on(async {
+            match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+                Ok(Some(CommandEvent::Stdout(line))) => String::from_utf8(line.into())
+                    .unwrap()
+                    .trim()
+                    .parse()
+                    .unwrap(),
+                event => panic!("unexpected event {event:?}"),
             }
-            Err(e) => {
-                let tx_ = tx.clone();
-                let _ = block_on_task(
-                    async move { tx_.send(CommandEvent::Error(e.to_string())).await },
-                );
-                break;
+        });
+        child.kill_tree().unwrap();
+        // the shell might exit on its own once its children are killed, before it's killed itself
+        tauri::async_runtime::block_on(async move {
+            tokio::time::timeout(Duration::from_secs(5), collect_status(rx)).await
+        })
+        .expect("the descendants still hold the pipes open");
+
+        let stat = std::fs::read_to_string(format!("/proc/{descendant}/stat")).ok();
+        let state = stat
+            .as_deref()
+            .and_then(|stat| stat.rsplit(") ").next()?.chars().next());
+        assert!(matches!(state, None | Some('Z')));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_cmd_contain() {
+        // the grandchild inherits the stderr pipe, so the event stream only closes once it exits
+        let cmd = Command::new("cmd")
+            .args(["/C", "ping -n 30 127.0.0.1 > nul"])
+            .contain(true);
+        let (mut rx, child) = cmd.spawn().unwrap();
+        // dropping the job kills the grandchild too
+        child.kill().unwrap();
+        tauri::async_runtime::block_on(async move {
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("the grandchild is still running"),
+                    Err(_) => break,
+                }
             }
-        }
+        });
     }
-}
 
-fn spawn_pipe_reader<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
-    tx: Sender<CommandEvent>,
-    guard: Arc<RwLock<()>>,
-    pipe_reader: PipeReader,
-    wrapper: F,
-    raw_out: bool,
-) {
-    spawn(move || {
-        let _lock = guard.read().unwrap();
-        let reader = BufReader::new(pipe_reader);
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_stdin_file() {
+        let path = std::env::temp_dir().join(format!("tauri-shell-stdin-{}", std::process::id()));
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
 
-        if raw_out {
-            read_raw_bytes(reader, tx, wrapper);
-        } else {
-            read_line(reader, tx, wrapper);
-        }
-    });
-}
+        let cmd = Command::new("cat")
+            .stdin_file(path.clone())
+            .set_raw_out(true)
+            .event_buffer(64);
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let stdout = tauri::async_runtime::block_on(async move {
+            let mut stdout = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(chunk) = event {
+                    stdout.extend(chunk);
+                }
+            }
+            stdout
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert!(stdout == data, "the output doesn't match the file");
+
+        let cmd = Command::new("cat").stdin_file(path);
+        assert!(cmd.spawn().is_err());
+
+        let cmd = Command::new("cat").stdin_bytes(b"bytes\n".to_vec());
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["bytes"]);
+    }
 
-// tests for the commands functions.
-#[cfg(test)]
-mod tests {
     #[cfg(not(windows))]
-    use super::*;
+    #[test]
+    fn test_cmd_stdout_file() {
+        let path = std::env::temp_dir().join(format!("tauri-shell-stdout-{}", std::process::id()));
+        std::fs::write(&path, "previous content").unwrap();
+        let cmd = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .stdout_file(path.clone());
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
+            while let Some(event) = rx.recv().await {
+                events.push(event);
+            }
+            events
+        });
+        assert!(matches!(
+            events.as_slice(),
+            [CommandEvent::Stderr(line), CommandEvent::Terminated(_)] if line == &b"err\n"[..]
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\n");
+        std::fs::remove_file(&path).unwrap();
+    }
 
     #[cfg(not(windows))]
     #[test]
-    fn test_cmd_spawn_output() {
-        let cmd = Command::new("cat").args(["test/test.txt"]);
-        let (mut rx, _) = cmd.spawn().unwrap();
+    fn test_cmd_inherit_stdio() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo inherited; exit 2"])
+            .inherit_stdio();
+        let (mut rx, mut child) = cmd.spawn().unwrap();
+        assert!(child.write(b"input").is_err());
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
+            while let Some(event) = rx.recv().await {
+                events.push(event);
+            }
+            events
+        });
+        assert!(matches!(
+            events.as_slice(),
+            [CommandEvent::Terminated(TerminatedPayload {
+                code: Some(2),
+                ..
+            })]
+        ));
+    }
 
-        tauri::async_runtime::block_on(async move {
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_null_stdio() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .null_stdio();
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
             while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Terminated(payload) => {
-                        assert_eq!(payload.code, Some(0));
-                    }
-                    CommandEvent::Stdout(line) => {
-                        assert_eq!(String::from_utf8(line).unwrap(), "This is a test doc!");
-                    }
-                    _ => {}
-                }
+                events.push(event);
             }
+            events
         });
+        assert!(matches!(
+            events.as_slice(),
+            [CommandEvent::Terminated(payload)] if payload.code == Some(0)
+        ));
     }
 
     #[cfg(not(windows))]
     #[test]
-    fn test_cmd_spawn_raw_output() {
-        let cmd = Command::new("cat").args(["test/test.txt"]);
-        let (mut rx, _) = cmd.spawn().unwrap();
+    fn test_cmd_status_keeps_stdout_file() {
+        let path = std::env::temp_dir().join(format!("shell-status-{}", std::process::id()));
+        let cmd = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .stdout_file(path.clone());
+        let status = tauri::async_runtime::block_on(cmd.status()).unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\n");
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        tauri::async_runtime::block_on(async move {
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_merge_stderr_into_stdout() {
+        let cmd = Command::new("sh")
+            .args(["-c", "echo 1; echo 2 >&2; echo 3; echo 4 >&2"])
+            .merge_stderr_into_stdout();
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
             while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Terminated(payload) => {
-                        assert_eq!(payload.code, Some(0));
-                    }
-                    CommandEvent::Stdout(line) => {
-                        assert_eq!(String::from_utf8(line).unwrap(), "This is a test doc!");
+                events.push(event);
+            }
+            events
+        });
+        let lines = events
+            .iter()
+            .map(|event| match event {
+                CommandEvent::Stdout(line) => String::from_utf8_lossy(line).into_owned(),
+                CommandEvent::Terminated(_) => "terminated".into(),
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["1\n", "2\n", "3\n", "4\n", "terminated"]);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_status_crash_signal() {
+        let crash = || Command::new("sh").args(["-c", "kill -SEGV $$"]);
+
+        let status = tauri::async_runtime::block_on(crash().status()).unwrap();
+        assert_eq!(status.code(), None);
+        assert_eq!(status.signal(), Some(libc::SIGSEGV));
+        assert!(!status.success());
+
+        let output = tauri::async_runtime::block_on(crash().output()).unwrap();
+        assert_eq!(output.status.code(), None);
+        assert_eq!(output.status.signal(), Some(libc::SIGSEGV));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_clone_spawns_independently() {
+        let cmd = Command::new("cat").stdin_str("input\n".into());
+        let outputs = tauri::async_runtime::block_on(async move {
+            let mut outputs = Vec::new();
+            for _ in 0..3 {
+                outputs.push(cmd.clone().stdout_lines().await.unwrap());
+            }
+            outputs
+        });
+        for lines in outputs {
+            assert_eq!(lines, ["input"]);
+        }
+    }
+
+    #[test]
+    fn test_cmd_getters() {
+        let cmd = Command::new("program")
+            .args(["a", "b"])
+            .env("KEY", "value")
+            .env_remove("OTHER")
+            .current_dir("dir");
+        assert_eq!(cmd.get_program(), "program");
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(
+            cmd.get_envs().collect::<Vec<_>>(),
+            [
+                (OsStr::new("KEY"), Some(OsStr::new("value"))),
+                (OsStr::new("OTHER"), None)
+            ]
+        );
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("dir")));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_from_std() {
+        let mut std_command = StdCommand::new("sh");
+        std_command
+            .args(["-c", "echo $KEY; pwd"])
+            .env("KEY", "value")
+            .env_remove("HOME")
+            .current_dir("/");
+        let cmd = Command::from_std(std_command);
+        assert_eq!(cmd.get_program(), "sh");
+        assert_eq!(
+            cmd.get_envs().collect::<Vec<_>>(),
+            [
+                (OsStr::new("HOME"), None),
+                (OsStr::new("KEY"), Some(OsStr::new("value")))
+            ]
+        );
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, ["value", "/"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_priority() {
+        let nice = |cmd: Command| {
+            let stat = tauri::async_runtime::block_on(cmd.args(["/proc/self/stat"]).stdout_lines())
+                .unwrap()
+                .remove(0);
+            // the nice value is the 17th field after the command name
+            stat.rsplit_once(") ")
+                .unwrap()
+                .1
+                .split(' ')
+                .nth(16)
+                .unwrap()
+                .parse::<i32>()
+                .unwrap()
+        };
+        assert_eq!(nice(Command::new("cat").priority(ProcessPriority::Low)), 10);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_rlimit() {
+        let cmd = Command::new("sh")
+            .args(["-c", "ulimit -n; while :; do :; done"])
+            .rlimit(Resource::NoFile, 64, 64)
+            .rlimit(Resource::Cpu, 1, 10);
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(10))
+                    .await
+                    .unwrap()
+                {
+                    Some(event @ CommandEvent::Terminated(_)) => {
+                        events.push(event);
+                        break events;
                     }
-                    _ => {}
+                    Some(event) => events.push(event),
+                    None => panic!("child was not stopped by the CPU limit"),
                 }
             }
         });
+        assert!(matches!(
+            events.as_slice(),
+            [CommandEvent::Stdout(line), CommandEvent::Terminated(payload)]
+                if line == &b"64\n"[..] && payload.signal == Some(libc::SIGXCPU)
+        ));
     }
 
     #[cfg(not(windows))]
     #[test]
-    // test the failure case
-    fn test_cmd_spawn_fail() {
-        let cmd = Command::new("cat").args(["test/"]);
-        let (mut rx, _) = cmd.spawn().unwrap();
+    fn test_cmd_resolve_in() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("shell-resolve-in-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let echo = dir.join("echo");
+        std::fs::write(&echo, "#!/bin/sh\nprintf 'bundled %s\\n' \"$0\"\n").unwrap();
+        std::fs::set_permissions(&echo, std::fs::Permissions::from_mode(0o755)).unwrap();
 
-        tauri::async_runtime::block_on(async move {
-            while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Terminated(payload) => {
-                        assert_eq!(payload.code, Some(1));
-                    }
-                    CommandEvent::Stderr(line) => {
-                        assert_eq!(
-                            String::from_utf8(line).unwrap(),
-                            "cat: test/: Is a directory\n"
-                        );
+        let cmd = Command::new("echo")
+            .resolve_in(dir.join("missing"))
+            .resolve_in(dir.clone());
+        let lines = tauri::async_runtime::block_on(cmd.stdout_lines()).unwrap();
+        assert_eq!(lines, [format!("bundled {}", echo.display())]);
+
+        let err = Command::new("tauri-shell-missing")
+            .resolve_in(dir.clone())
+            .spawn()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "program not found, tried {}, tauri-shell-missing",
+                dir.join("tauri-shell-missing").display()
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_sidecar_target_triple() {
+        let exe_dir = platform::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .to_path_buf();
+        let name = format!("shell-sidecar-{}", std::process::id());
+        let suffixed = exe_dir.join(format!("{name}-{}", platform::target_triple().unwrap()));
+
+        // falls back to the plain path when neither exists
+        let cmd = Command::new_sidecar(&name).unwrap();
+        assert_eq!(cmd.get_program(), exe_dir.join(&name));
+
+        std::fs::write(&suffixed, "").unwrap();
+        let cmd = Command::new_sidecar(&name).unwrap();
+        assert_eq!(cmd.get_program(), suffixed);
+
+        // the plain name takes precedence
+        std::fs::write(exe_dir.join(&name), "").unwrap();
+        let cmd = Command::new_sidecar(&name).unwrap();
+        assert_eq!(cmd.get_program(), exe_dir.join(&name));
+
+        std::fs::remove_file(&suffixed).unwrap();
+        std::fs::remove_file(exe_dir.join(&name)).unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_write_str_encoding() {
+        let hex = |cmd: Command| {
+            let (mut rx, mut child) = cmd.spawn().unwrap();
+            child.write_str("日").unwrap();
+            child.close_stdin().unwrap();
+            tauri::async_runtime::block_on(async move {
+                let mut out = String::new();
+                while let Some(event) = rx.recv().await {
+                    if let CommandEvent::Stdout(line) = event {
+                        out.push_str(&String::from_utf8_lossy(&line));
                     }
-                    _ => {}
+                }
+                out.split_whitespace().collect::<Vec<_>>().join(" ")
+            })
+        };
+        let od = || Command::new("od").args(["-An", "-tx1"]);
+        assert_eq!(hex(od()), "e6 97 a5");
+        assert_eq!(hex(od().encoding(encoding_rs::SHIFT_JIS)), "93 fa");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_max_line_len() {
+        let cmd = Command::new("sh")
+            .args(["-c", "head -c 100000 /dev/zero; echo end"])
+            .max_line_len(1000);
+        let (mut rx, _child) = cmd.spawn().unwrap();
+        let lines = tauri::async_runtime::block_on(async move {
+            let mut lines = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    lines.push(line);
                 }
             }
+            lines
         });
+        assert!(lines.iter().all(|line| line.len() <= 1000));
+        assert_eq!(lines.iter().map(Bytes::len).sum::<usize>(), 100004);
+        assert_eq!(lines.last().unwrap(), &b"end\n"[..]);
     }
 
     #[cfg(not(windows))]
     #[test]
-    // test the failure case (raw encoding)
-    fn test_cmd_spawn_raw_fail() {
-        let cmd = Command::new("cat").args(["test/"]);
-        let (mut rx, _) = cmd.spawn().unwrap();
-
-        tauri::async_runtime::block_on(async move {
+    fn test_cmd_spawn_timestamped() {
+        let spawned_at = Instant::now();
+        let (mut rx, _child) = Command::new("sh")
+            .args(["-c", "echo first; sleep 0.3; echo second"])
+            .spawn_timestamped()
+            .unwrap();
+        let events = tauri::async_runtime::block_on(async move {
+            let mut events = Vec::new();
             while let Some(event) = rx.recv().await {
-                match event {
-                    CommandEvent::Terminated(payload) => {
-                        assert_eq!(payload.code, Some(1));
+                // a slow receiver doesn't delay the timestamps
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                events.push(event);
+            }
+            events
+        });
+        let at = |expected: &[u8]| {
+            events
+                .iter()
+                .find_map(|(at, event)| match event {
+                    CommandEvent::Stdout(line) if line == expected => Some(*at - spawned_at),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let (first, second) = (at(b"first\n"), at(b"second\n"));
+        assert!(first < Duration::from_millis(250), "{first:?}");
+        assert!(
+            second - first >= Duration::from_millis(250),
+            "{first:?} {second:?}"
+        );
+        assert!(matches!(
+            events.last(),
+            Some((_, CommandEvent::Terminated(_)))
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_broadcast() {
+        let (events, _child) = Command::new("sh")
+            .args(["-c", "echo a; echo b"])
+            .spawn_broadcast(16)
+            .unwrap();
+        let collect = |mut rx: broadcast::Receiver<CommandEvent>| async move {
+            let mut lines = Vec::new();
+            loop {
+                match rx.recv().await {
+                    Ok(CommandEvent::Stdout(line)) => lines.push(line),
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Closed) => break lines,
+                    Err(e) => panic!("{e}"),
+                }
+            }
+        };
+        let (first, second) = (events.subscribe(), events.subscribe());
+        let (first, second) =
+            tauri::async_runtime::block_on(
+                async move { (collect(first).await, collect(second).await) },
+            );
+        assert_eq!(first, [b"a\n".to_vec(), b"b\n".to_vec()]);
+        // subscribed right after spawning, so it might have missed some of the output
+        assert!(first.ends_with(&second));
+
+        // the stream is closed for late subscribers
+        let late = tauri::async_runtime::block_on(collect(events.subscribe()));
+        assert!(late.is_empty());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_preserves_bytes() {
+        let cmd = Command::new("printf").args([r"a\rb\r\nc"]);
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+        assert_eq!(output.stdout, b"a\rb\r\nc");
+        assert_eq!(output.stdout_total, 6);
+    }
+
+    #[cfg(all(unix, feature = "pty"))]
+    #[test]
+    fn test_cmd_spawn_pty() {
+        let (mut rx, mut child) = Command::new("sh")
+            .args([
+                "-c",
+                "test -t 0 && test -t 1 && echo tty; stty size; read x; stty size",
+            ])
+            .process_group(true)
+            .spawn_pty(24, 80)
+            .unwrap();
+        let lines = tauri::async_runtime::block_on(async move {
+            let mut lines = Vec::new();
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(5)).await {
+                    Ok(Some(CommandEvent::Stdout(line))) => {
+                        if line == b"24 80\r\n"[..] {
+                            child.resize(40, 120).unwrap();
+                            child.write_line("resized").unwrap();
+                        }
+                        lines.push(String::from_utf8(line.into()).unwrap());
                     }
-                    CommandEvent::Stderr(line) => {
-                        assert_eq!(
-                            String::from_utf8(line).unwrap(),
-                            "cat: test/: Is a directory\n"
-                        );
+                    Ok(Some(CommandEvent::Terminated(payload))) => {
+                        assert_eq!(payload.code, Some(0));
+                        break;
                     }
-                    _ => {}
+                    Ok(Some(_)) => {}
+                    Ok(None) => panic!("timed out, received {lines:?}"),
+                    Err(_) => break,
                 }
             }
+            lines
         });
+        assert_eq!(lines[..2], ["tty\r\n", "24 80\r\n"]);
+        assert_eq!(lines.last().unwrap(), "40 120\r\n");
     }
 
     #[cfg(not(windows))]
     #[test]
-    fn test_cmd_output_output() {
-        let cmd = Command::new("cat").args(["test/test.txt"]);
-        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
-
-        assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+    fn test_cmd_shell() {
+        let dir = std::env::temp_dir();
+        let command =
+            Command::shell("printf '%s\\n' \"$VALUE\" | tr a-z A-Z; pwd >&2; echo \"$0\"")
+                .env("VALUE", "a; echo injected")
+                .current_dir(&dir)
+                .args(["arg"]);
+        let output = tauri::async_runtime::block_on(command.output()).unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"A; ECHO INJECTED\narg\n");
+        let pwd = String::from_utf8(output.stderr).unwrap();
         assert_eq!(
-            String::from_utf8(output.stdout).unwrap(),
-            "This is a test doc!\n"
+            Path::new(pwd.trim_end()).canonicalize().unwrap(),
+            dir.canonicalize().unwrap()
         );
     }
 
-    #[cfg(not(windows))]
     #[test]
-    fn test_cmd_output_output_fail() {
-        let cmd = Command::new("cat").args(["test/"]);
-        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+    fn test_cmd_parse() {
+        let command = Command::parse(r#"foo "a b" 'c d' e\ f"#).unwrap();
+        assert_eq!(command.get_program(), "foo");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["a b", "c d", "e f"]
+        );
 
-        assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+        let args = |cmdline: &str| {
+            let command = Command::parse(cmdline).unwrap();
+            command
+                .get_args()
+                .map(|arg| arg.to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(args("  foo\ta  \n b "), ["a", "b"]);
+        assert_eq!(args(r#"foo "" '' a""b"#), ["", "", "ab"]);
         assert_eq!(
-            String::from_utf8(output.stderr).unwrap(),
-            "cat: test/: Is a directory\n\n"
+            args(r#"foo 'a\"b' "a'b" "\$x \n \\ \"""#),
+            [r#"a\"b"#, "a'b", r#"$x \n \ ""#]
         );
+        assert_eq!(args("foo \\'a b\\\" $HOME *"), ["'a", "b\"", "$HOME", "*"]);
+        assert_eq!(args("foo a\\\nb \"c\\\nd\""), ["ab", "cd"]);
+
+        assert_eq!(Command::parse("''x").unwrap().get_program(), "x");
+        for cmdline in ["", "  \n", "foo 'a", "foo \"a", "foo a\\"] {
+            assert!(
+                matches!(
+                    Command::parse(cmdline),
+                    Err(crate::Error::InvalidCommandLine(_))
+                ),
+                "{cmdline:?}"
+            );
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_with_cancel() {
+        let terminated = |mut rx: Receiver<CommandEvent>| {
+            tauri::async_runtime::block_on(async move {
+                loop {
+                    match recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap() {
+                        Some(CommandEvent::Terminated(payload)) => break payload,
+                        Some(_) => {}
+                        None => panic!("the process did not terminate"),
+                    }
+                }
+            })
+        };
+
+        let token = CancellationToken::new();
+        let (rx, _child) = Command::new("sleep")
+            .args(["30"])
+            .spawn_with_cancel(token.child_token())
+            .unwrap();
+        token.cancel();
+        let payload = terminated(rx);
+        assert_eq!(payload.code, None);
+        assert_eq!(payload.signal, Some(CANCELLED_SIGNAL));
+
+        // cancelling after the process exited changes nothing
+        let token = CancellationToken::new();
+        let (rx, child) = Command::new("true")
+            .spawn_with_cancel(token.clone())
+            .unwrap();
+        tauri::async_runtime::block_on(child.wait()).unwrap();
+        token.cancel();
+        assert_eq!(terminated(rx).code, Some(0));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_is_alive() {
+        let (mut rx, child) = Command::new("sleep").args(["1"]).spawn().unwrap();
+        assert!(child.is_alive());
+        let payload = tauri::async_runtime::block_on(async move {
+            let status = child.wait().await.unwrap();
+            assert!(!child.is_alive());
+            assert_eq!(status.code(), Some(0));
+            loop {
+                match recv_timeout(&mut rx, Duration::from_secs(5)).await.unwrap() {
+                    Some(CommandEvent::Terminated(payload)) => break payload,
+                    Some(_) => {}
+                    None => panic!("the termination was not emitted"),
+                }
+            }
+        });
+        assert_eq!(payload.code, Some(0));
+    }
+
+    /// The CPU time spent by the test process so far, including the runtime and reader threads.
+    #[cfg(unix)]
+    fn cpu_time() -> Duration {
+        // SAFETY: getrusage only writes the usage of the process to the struct
+        let usage = unsafe {
+            let mut usage = std::mem::zeroed::<libc::rusage>();
+            libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+            usage
+        };
+        let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+        time(usage.ru_utime) + time(usage.ru_stime)
+    }
+
+    /// Receives the output of a child printing 100k lines, reporting how long it takes and the CPU time spent.
+    ///
+    /// Run with `cargo test --release --lib bench_chatty_child -- --ignored --nocapture`.
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_chatty_child() {
+        const LINES: usize = 100_000;
+        for event_buffer in [1, 1024] {
+            let cmd = Command::new("seq")
+                .args([LINES.to_string()])
+                .event_buffer(event_buffer);
+            let cpu = cpu_time();
+            let spawned_at = Instant::now();
+            // received in a task, like an app consuming the events on the runtime
+            let receiver = tauri::async_runtime::spawn(async move {
+                let (mut rx, _child) = cmd.spawn().unwrap();
+                let mut lines = 0;
+                let mut first = None;
+                while let Some(event) = rx.recv().await {
+                    if let CommandEvent::Stdout(_) = event {
+                        lines += 1;
+                        first.get_or_insert_with(|| spawned_at.elapsed());
+                    }
+                }
+                (lines, first.unwrap())
+            });
+            let (lines, first) = tauri::async_runtime::block_on(receiver).unwrap();
+            let elapsed = spawned_at.elapsed();
+            assert_eq!(lines, LINES);
+            println!(
+                "event_buffer({event_buffer}): first line after {first:?}, all lines after {elapsed:?}, {:?} of CPU time",
+                cpu_time() - cpu
+            );
+        }
+    }
+
+    /// Runs 1000 trivial commands with [`Command::status`], which discards their output without pipes,
+    /// and with piped output awaited like [`Command::status`] did before.
+    ///
+    /// Run with `cargo test --release --lib bench_status -- --ignored --nocapture`.
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_status() {
+        const RUNS: usize = 1000;
+        tauri::async_runtime::block_on(async {
+            // warm up the runtime and the caches of the program lookup
+            for _ in 0..10 {
+                Command::new("true").status().await.unwrap();
+            }
+
+            let cpu = cpu_time();
+            let started_at = Instant::now();
+            for _ in 0..RUNS {
+                assert!(Command::new("true").status().await.unwrap().success());
+            }
+            println!(
+                "null stdio: {RUNS} runs in {:?}, {:?} of CPU time",
+                started_at.elapsed(),
+                cpu_time() - cpu
+            );
+
+            let cpu = cpu_time();
+            let started_at = Instant::now();
+            for _ in 0..RUNS {
+                let (rx, _child) = Command::new("true").spawn().unwrap();
+                assert!(collect_status(rx).await.success());
+            }
+            println!(
+                "piped stdio: {RUNS} runs in {:?}, {:?} of CPU time",
+                started_at.elapsed(),
+                cpu_time() - cpu
+            );
+        });
     }
 }
@@ -0,0 +1,134 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Prioritized delivery of the stdout and stderr events, see [`super::Command::stream_priority`].
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::JoinHandle,
+};
+
+use tokio::sync::Notify;
+
+use super::{spawn_thread, CommandEvent, EventSender};
+
+/// The output stream whose events are delivered first when both have pending events.
+///
+/// See [`super::Command::stream_priority`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Deliver the stdout events first.
+    Stdout,
+    /// Deliver the stderr events first.
+    Stderr,
+}
+
+/// Merges the events of the stdout and stderr readers into the event channel,
+/// delivering the pending event of the prioritized stream first.
+#[derive(Debug, Default)]
+pub(crate) struct Dispatcher {
+    state: Mutex<State>,
+    /// Wakes the dispatcher thread once an event is queued or a sender is dropped.
+    changed: Condvar,
+    /// Wakes the reader tasks once a slot is free or the receiver is dropped.
+    dispatched: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The pending event of the prioritized stream and of the other one,
+    /// at most one each so the readers still wait while the receiver is not draining.
+    high: Option<CommandEvent>,
+    low: Option<CommandEvent>,
+    /// The number of readers still sending.
+    senders: usize,
+    /// Set once the receiver is dropped.
+    closed: bool,
+}
+
+/// The handle of a reader to the [`Dispatcher`].
+#[derive(Debug)]
+pub(crate) struct DispatchSender {
+    dispatcher: Arc<Dispatcher>,
+    high: bool,
+}
+
+impl Dispatcher {
+    /// Spawns the thread forwarding the events to `tx`, which exits once every sender is dropped.
+    pub(crate) fn spawn(tx: EventSender) -> (Arc<Self>, JoinHandle<()>) {
+        let dispatcher = Arc::new(Self::default());
+        let dispatcher_ = dispatcher.clone();
+        let thread = spawn_thread(move || dispatcher_.run(tx));
+        (dispatcher, thread)
+    }
+
+    /// Creates a sender for the prioritized stream if `high` is set, or for the other stream.
+    pub(crate) fn sender(self: &Arc<Self>, high: bool) -> DispatchSender {
+        self.state.lock().unwrap().senders += 1;
+        DispatchSender {
+            dispatcher: self.clone(),
+            high,
+        }
+    }
+
+    fn run(&self, tx: EventSender) {
+        loop {
+            let event = {
+                let mut state = self.state.lock().unwrap();
+                loop {
+                    if let Some(event) = state.high.take().or_else(|| state.low.take()) {
+                        self.dispatched.notify_waiters();
+                        break event;
+                    }
+                    if state.senders == 0 {
+                        return;
+                    }
+                    state = self.changed.wait(state).unwrap();
+                }
+            };
+            // the other reader fills its slot while this blocks, so the next pick honors the priority
+            if tx.send(event).is_err() {
+                self.state.lock().unwrap().closed = true;
+                self.dispatched.notify_waiters();
+                return;
+            }
+        }
+    }
+}
+
+impl DispatchSender {
+    /// Queues the event, waiting for the previous one of the same stream to be dispatched.
+    /// Fails if the receiver was dropped.
+    pub(crate) async fn send(&self, event: CommandEvent) -> Result<(), CommandEvent> {
+        let dispatcher = &self.dispatcher;
+        loop {
+            // created before checking the slot, so a dispatch in between still wakes it up
+            let dispatched = dispatcher.dispatched.notified();
+            {
+                let mut state = dispatcher.state.lock().unwrap();
+                if state.closed {
+                    return Err(event);
+                }
+                let slot = if self.high {
+                    &mut state.high
+                } else {
+                    &mut state.low
+                };
+                if slot.is_none() {
+                    slot.replace(event);
+                    dispatcher.changed.notify_all();
+                    return Ok(());
+                }
+            }
+            dispatched.await;
+        }
+    }
+}
+
+impl Drop for DispatchSender {
+    fn drop(&mut self) {
+        self.dispatcher.state.lock().unwrap().senders -= 1;
+        self.dispatcher.changed.notify_all();
+    }
+}
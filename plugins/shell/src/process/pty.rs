@@ -0,0 +1,119 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Pseudo-terminals for [`super::Command::spawn_pty`].
+
+use std::{
+    fs::File,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// A pseudo-terminal, the child is attached to the slave side and the parent uses the master side.
+pub(crate) struct Pty {
+    pub(crate) master: File,
+    pub(crate) slave: File,
+}
+
+impl Pty {
+    /// Opens a pseudo-terminal with the given window size.
+    pub(crate) fn open(rows: u16, cols: u16) -> io::Result<Self> {
+        let mut master = -1;
+        let mut slave = -1;
+        let mut size = window_size(rows, cols);
+        // SAFETY: the out pointers are valid, and the name and termios are optional
+        let result = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                // mutable on macOS, const on Linux
+                &mut size as *mut _,
+            )
+        };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: openpty succeeded, so both descriptors are open and owned by us
+        let (master, slave) =
+            unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+        // openpty doesn't set close-on-exec, the child only gets the slave as its standard streams
+        set_cloexec(&master)?;
+        set_cloexec(&slave)?;
+        Ok(Self {
+            master: master.into(),
+            slave: slave.into(),
+        })
+    }
+}
+
+/// Changes the window size of the pseudo-terminal, which sends `SIGWINCH` to its foreground process group.
+pub(crate) fn resize(master: &File, rows: u16, cols: u16) -> io::Result<()> {
+    let size = window_size(rows, cols);
+    // SAFETY: TIOCSWINSZ only reads the window size
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Makes the pseudo-terminal on stdin the controlling terminal of the child in a new session.
+///
+/// Runs in the child between fork and exec, so it must only call async-signal-safe functions.
+pub(crate) fn set_controlling_terminal() -> io::Result<()> {
+    // SAFETY: setsid and ioctl are async-signal-safe
+    unsafe {
+        if libc::setsid() == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        #[allow(clippy::useless_conversion)]
+        if libc::ioctl(0, libc::TIOCSCTTY.into(), 0) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn window_size(rows: u16, cols: u16) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn set_cloexec(fd: &OwnedFd) -> io::Result<()> {
+    // SAFETY: fcntl only updates the flags of the descriptor
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFD);
+        if flags == -1 || libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, flags | libc::FD_CLOEXEC) == -1
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Reads the master side of a pseudo-terminal, ending the stream once the slave side is closed.
+pub(crate) struct PtyReader<R>(pub(crate) R);
+
+impl<R: AsyncRead + Unpin> AsyncRead for PtyReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match Pin::new(&mut self.0).poll_read(cx, buf) {
+            // Linux fails with EIO instead of returning EOF once every descriptor of the slave is closed
+            Poll::Ready(Err(e)) if e.raw_os_error() == Some(libc::EIO) => Poll::Ready(Ok(())),
+            poll => poll,
+        }
+    }
+}
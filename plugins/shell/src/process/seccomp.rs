@@ -0,0 +1,267 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Seccomp-bpf syscall filters applied to child processes on Linux.
+
+use libc::{c_long, sock_filter};
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xc000_003e;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xc000_00b7;
+
+/// Syscalls numbers with this bit set use the x32 ABI, which would bypass the filter.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+// offsets in `struct seccomp_data`
+const NR_OFFSET: u32 = 0;
+const ARCH_OFFSET: u32 = 4;
+const ARGS_OFFSET: u32 = 16;
+
+/// The flags of the `open` family of syscalls that allow modifying a file.
+const WRITE_FLAGS: i32 =
+    libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC | libc::O_APPEND;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    /// Fail the syscall with `errno`.
+    Deny { syscall: c_long, errno: i32 },
+    /// Fail the syscall with `EPERM` if its `arg` argument has any of the `flags` bits set.
+    DenyFlags {
+        syscall: c_long,
+        arg: u32,
+        flags: u32,
+    },
+}
+
+/// A seccomp-bpf filter denying syscalls to the child process, see [`super::Command::seccomp`].
+///
+/// Denied syscalls fail with `EPERM` instead of killing the process, so well-behaved programs can report the error.
+/// Only supported on `x86_64` and `aarch64`.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use tauri_plugin_shell::{process::SeccompFilter, ShellExt};
+/// tauri::Builder::default()
+///   .setup(|app| {
+///     let filter = SeccompFilter::no_network().merge(SeccompFilter::read_only_fs());
+///     let (_rx, _child) = app.shell().sidecar("untrusted-helper")?.seccomp(filter).spawn()?;
+///     Ok(())
+///   });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SeccompFilter {
+    rules: Vec<Rule>,
+}
+
+impl SeccompFilter {
+    /// Creates an empty filter, allowing every syscall.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Denies the given syscall, e.g. `libc::SYS_ptrace`.
+    #[must_use]
+    pub fn deny(mut self, syscall: c_long) -> Self {
+        self.rules.push(Rule::Deny {
+            syscall,
+            errno: libc::EPERM,
+        });
+        self
+    }
+
+    /// Adds the rules of `other` to this filter.
+    #[must_use]
+    pub fn merge(mut self, other: SeccompFilter) -> Self {
+        self.rules.extend(other.rules);
+        self
+    }
+
+    /// A filter denying the creation and use of sockets, including Unix domain sockets.
+    ///
+    /// Sockets inherited from the parent process can still be read and written.
+    pub fn no_network() -> Self {
+        [
+            libc::SYS_socket,
+            libc::SYS_connect,
+            libc::SYS_bind,
+            libc::SYS_listen,
+            libc::SYS_accept,
+            libc::SYS_accept4,
+        ]
+        .into_iter()
+        .fold(Self::new(), Self::deny)
+    }
+
+    /// A filter denying the syscalls that create, modify or delete files and directories.
+    ///
+    /// Files can still be opened for reading. File descriptors inherited from the parent process,
+    /// such as the stdout and stderr pipes, can still be written to.
+    pub fn read_only_fs() -> Self {
+        let mut filter = [
+            libc::SYS_mkdirat,
+            libc::SYS_unlinkat,
+            libc::SYS_renameat2,
+            libc::SYS_linkat,
+            libc::SYS_symlinkat,
+            libc::SYS_mknodat,
+            libc::SYS_fchmod,
+            libc::SYS_fchmodat,
+            libc::SYS_fchown,
+            libc::SYS_fchownat,
+            libc::SYS_truncate,
+            libc::SYS_ftruncate,
+            libc::SYS_fallocate,
+            libc::SYS_utimensat,
+        ]
+        .into_iter()
+        .fold(Self::new(), Self::deny);
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            filter = [
+                libc::SYS_creat,
+                libc::SYS_mkdir,
+                libc::SYS_rmdir,
+                libc::SYS_unlink,
+                libc::SYS_rename,
+                libc::SYS_renameat,
+                libc::SYS_link,
+                libc::SYS_symlink,
+                libc::SYS_mknod,
+                libc::SYS_chmod,
+                libc::SYS_chown,
+                libc::SYS_lchown,
+                libc::SYS_utime,
+                libc::SYS_utimes,
+                libc::SYS_futimesat,
+            ]
+            .into_iter()
+            .fold(filter, Self::deny);
+            filter.rules.push(Rule::DenyFlags {
+                syscall: libc::SYS_open,
+                arg: 1,
+                flags: WRITE_FLAGS as u32,
+            });
+        }
+
+        filter.rules.push(Rule::DenyFlags {
+            syscall: libc::SYS_openat,
+            arg: 2,
+            flags: WRITE_FLAGS as u32,
+        });
+        // the flags of openat2 are in a struct the filter can't inspect,
+        // pretend it doesn't exist so the C library falls back to openat
+        filter.rules.push(Rule::Deny {
+            syscall: libc::SYS_openat2,
+            errno: libc::ENOSYS,
+        });
+
+        filter
+    }
+
+    /// Compiles the filter to a BPF program.
+    fn compile(&self) -> Vec<sock_filter> {
+        let mut program = vec![
+            load(ARCH_OFFSET),
+            jump(libc::BPF_JEQ, AUDIT_ARCH, 1, 0),
+            ret(libc::SECCOMP_RET_KILL_PROCESS),
+            load(NR_OFFSET),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        program.extend([
+            jump(libc::BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+            ret(libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+        ]);
+
+        for rule in &self.rules {
+            match *rule {
+                Rule::Deny { syscall, errno } => program.extend([
+                    jump(libc::BPF_JEQ, syscall as u32, 0, 1),
+                    ret(libc::SECCOMP_RET_ERRNO | errno as u32),
+                ]),
+                Rule::DenyFlags {
+                    syscall,
+                    arg,
+                    flags,
+                } => program.extend([
+                    jump(libc::BPF_JEQ, syscall as u32, 0, 4),
+                    // the lower 32 bits of the argument on little endian architectures
+                    load(ARGS_OFFSET + arg * 8),
+                    jump(libc::BPF_JSET, flags, 0, 1),
+                    ret(libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+                    load(NR_OFFSET),
+                ]),
+            }
+        }
+
+        program.push(ret(libc::SECCOMP_RET_ALLOW));
+        program
+    }
+
+    /// Returns a function that installs the filter on the current process, to be called in `pre_exec`.
+    ///
+    /// The program is compiled beforehand since allocating is not allowed after forking.
+    pub(crate) fn installer(&self) -> impl FnMut() -> std::io::Result<()> + Send + Sync + 'static {
+        let mut program = self.compile();
+        move || {
+            let prog = libc::sock_fprog {
+                len: program.len() as u16,
+                filter: program.as_mut_ptr(),
+            };
+            // SAFETY: prctl is async-signal-safe and `prog` outlives the calls
+            unsafe {
+                // required to install a filter without CAP_SYS_ADMIN
+                if libc::prctl(
+                    libc::PR_SET_NO_NEW_PRIVS,
+                    1 as libc::c_ulong,
+                    0 as libc::c_ulong,
+                    0 as libc::c_ulong,
+                    0 as libc::c_ulong,
+                ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER as libc::c_ulong,
+                    &prog as *const libc::sock_fprog,
+                ) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn load(offset: u32) -> sock_filter {
+    sock_filter {
+        code: (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16,
+        jt: 0,
+        jf: 0,
+        k: offset,
+    }
+}
+
+fn jump(op: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: (libc::BPF_JMP | op | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+fn ret(k: u32) -> sock_filter {
+    sock_filter {
+        code: (libc::BPF_RET | libc::BPF_K) as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Enumerates and kills the descendants of a process, see [`super::CommandChild::kill_tree`].
+
+use std::io;
+
+/// Kills the descendants of the process `pid`, deepest first, leaving the process itself running.
+///
+/// Each process is checked to still be the child of the expected parent right before it's killed,
+/// so a pid reused after a descendant exited is left alone.
+#[cfg(unix)]
+pub(crate) fn kill_descendants(pid: u32) -> io::Result<()> {
+    let processes = processes()?;
+    // every process is listed after its parent, so the list is reversed to kill the deepest ones first
+    let mut descendants = Vec::new();
+    let mut parents = vec![pid];
+    while let Some(parent) = parents.pop() {
+        for &(child, _) in processes.iter().filter(|(_, ppid)| *ppid == parent) {
+            descendants.push((child, parent));
+            parents.push(child);
+        }
+    }
+
+    for (child, parent) in descendants.into_iter().rev() {
+        if parent_pid(child) != Some(parent) {
+            continue;
+        }
+        // SAFETY: kill only takes the pid and signal
+        if unsafe { libc::kill(child as libc::pid_t, libc::SIGKILL) } == -1 {
+            let e = io::Error::last_os_error();
+            // the process exited in the meantime
+            if e.raw_os_error() != Some(libc::ESRCH) {
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Kills the process `pid` and all of its descendants with `taskkill /T`.
+#[cfg(windows)]
+pub(crate) fn kill_tree(pid: u32) -> io::Result<()> {
+    use std::os::windows::process::CommandExt;
+    // fails if the process already exited, which is fine since its descendants can't be found anymore
+    std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID"])
+        .arg(pid.to_string())
+        .creation_flags(super::CREATE_NO_WINDOW)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+    Ok(())
+}
+
+/// Returns the pid and parent pid of every running process.
+#[cfg(target_os = "linux")]
+fn processes() -> io::Result<Vec<(u32, u32)>> {
+    let mut processes = Vec::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let pid = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok());
+        // the process might exit while enumerating
+        if let Some((pid, ppid)) = pid.and_then(|pid| Some((pid, parent_pid(pid)?))) {
+            processes.push((pid, ppid));
+        }
+    }
+    Ok(processes)
+}
+
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // the command name can contain spaces and parentheses, the state and parent pid follow it
+    stat.rsplit_once(") ")?.1.split(' ').nth(1)?.parse().ok()
+}
+
+/// Returns the pid and parent pid of every running process.
+#[cfg(all(unix, not(target_os = "linux")))]
+fn processes() -> io::Result<Vec<(u32, u32)>> {
+    Ok(parse_ps(&ps(&["-A", "-o", "pid=", "-o", "ppid="])?))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parent_pid(pid: u32) -> Option<u32> {
+    let output = ps(&["-o", "ppid=", "-p", &pid.to_string()]).ok()?;
+    output.trim().parse().ok()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn ps(args: &[&str]) -> io::Result<String> {
+    let output = std::process::Command::new("ps")
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parse_ps(output: &str) -> Vec<(u32, u32)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            Some((fields.next()?.parse().ok()?, fields.next()?.parse().ok()?))
+        })
+        .collect()
+}
@@ -0,0 +1,164 @@
+// Copyright 2019-2023 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Windows process APIs not exposed by the standard library.
+
+use std::{ffi::c_void, io};
+
+type Handle = *mut c_void;
+
+const PROCESS_TERMINATE: u32 = 0x0001;
+const PROCESS_SET_QUOTA: u32 = 0x0100;
+const PROCESS_SUSPEND_RESUME: u32 = 0x0800;
+const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION_CLASS: i32 = 9;
+
+#[repr(C)]
+#[derive(Default)]
+struct JobObjectBasicLimitInformation {
+    per_process_user_time_limit: i64,
+    per_job_user_time_limit: i64,
+    limit_flags: u32,
+    minimum_working_set_size: usize,
+    maximum_working_set_size: usize,
+    active_process_limit: u32,
+    affinity: usize,
+    priority_class: u32,
+    scheduling_class: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct IoCounters {
+    read_operation_count: u64,
+    write_operation_count: u64,
+    other_operation_count: u64,
+    read_transfer_count: u64,
+    write_transfer_count: u64,
+    other_transfer_count: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct JobObjectExtendedLimitInformation {
+    basic_limit_information: JobObjectBasicLimitInformation,
+    io_info: IoCounters,
+    process_memory_limit: usize,
+    job_memory_limit: usize,
+    peak_process_memory_used: usize,
+    peak_job_memory_used: usize,
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> Handle;
+    fn CloseHandle(handle: Handle) -> i32;
+    fn CreateJobObjectW(job_attributes: *mut c_void, name: *const u16) -> Handle;
+    fn SetInformationJobObject(
+        job: Handle,
+        information_class: i32,
+        information: *mut c_void,
+        information_length: u32,
+    ) -> i32;
+    fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+}
+
+// undocumented, but exported by ntdll since Windows XP
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtSuspendProcess(process: Handle) -> i32;
+    fn NtResumeProcess(process: Handle) -> i32;
+}
+
+/// Suspends every thread of the process.
+pub(crate) fn suspend_process(pid: u32) -> io::Result<()> {
+    // SAFETY: the handle is valid for the duration of the call
+    with_process(pid, |process| unsafe { NtSuspendProcess(process) })
+}
+
+/// Resumes every thread of the process.
+pub(crate) fn resume_process(pid: u32) -> io::Result<()> {
+    // SAFETY: the handle is valid for the duration of the call
+    with_process(pid, |process| unsafe { NtResumeProcess(process) })
+}
+
+/// A job object terminating the processes assigned to it once its handle is closed.
+#[derive(Debug)]
+pub(crate) struct JobObject(Handle);
+
+// SAFETY: job object handles can be used and closed from any thread
+unsafe impl Send for JobObject {}
+// SAFETY: the handle is only used on drop
+unsafe impl Sync for JobObject {}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // SAFETY: the handle is valid and owned
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Assigns the process, created suspended, to a new job object killing it along with its descendants
+/// once the job is dropped, then resumes the process.
+///
+/// The process must be suspended so it can't spawn processes outside of the job before it's assigned.
+pub(crate) fn contain_process(pid: u32) -> io::Result<JobObject> {
+    // SAFETY: the handles are checked before use, the job is owned by the returned value
+    // and the process handle is closed before returning
+    let job = unsafe {
+        let job = CreateJobObjectW(std::ptr::null_mut(), std::ptr::null());
+        if job.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let job = JobObject(job);
+
+        let mut info = JobObjectExtendedLimitInformation::default();
+        info.basic_limit_information.limit_flags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        if SetInformationJobObject(
+            job.0,
+            JOB_OBJECT_EXTENDED_LIMIT_INFORMATION_CLASS,
+            &mut info as *mut _ as *mut c_void,
+            std::mem::size_of::<JobObjectExtendedLimitInformation>() as u32,
+        ) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let assigned = AssignProcessToJobObject(job.0, process);
+        let error = io::Error::last_os_error();
+        CloseHandle(process);
+        if assigned == 0 {
+            return Err(error);
+        }
+        job
+    };
+    resume_process(pid)?;
+    Ok(job)
+}
+
+/// Opens the process with suspend/resume access and calls `f`, which returns an `NTSTATUS`.
+fn with_process(pid: u32, f: impl FnOnce(Handle) -> i32) -> io::Result<()> {
+    // SAFETY: OpenProcess has no preconditions, the handle is checked before use and closed afterwards
+    unsafe {
+        let process = OpenProcess(PROCESS_SUSPEND_RESUME, 0, pid);
+        if process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let status = f(process);
+        CloseHandle(process);
+        if status < 0 {
+            return Err(io::Error::other(format!(
+                "failed with NTSTATUS {:#010x}",
+                status as u32
+            )));
+        }
+    }
+    Ok(())
+}